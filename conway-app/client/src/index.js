@@ -3,25 +3,9 @@
 /*
  * Constants.
  */
-const WEBSOCKET_URL = 'ws://localhost:3012';
-
-const DEFAULT_PATTERN = `
-............x............
-............x............
-............x............
-xxx......................
-..x......................
-.x.......................
-.........................
-.....xxx..xxxxx..xxx.....
-.........................
-.......................x.
-......................x..
-......................xxx
-............x............
-............x............
-............x............
-`;
+const CONFIG_URL = '/api/config';
+const PATTERNS_URL = '/api/patterns';
+const DEFAULT_PATTERN_NAME = 'default';
 
 const DEFAULT_SETTINGS = Object.freeze({
     char_alive: '■',
@@ -32,8 +16,28 @@ const DEFAULT_SETTINGS = Object.freeze({
 const MSG_CONNECTED = 'Connected';
 const MSG_STATUS = 'Status';
 const MSG_GRID = 'Grid';
+const MSG_STATS = 'Stats';
+const MSG_SAVED = 'Saved';
 const MSG_ERROR = 'Error';
 
+const RESIZE_DEBOUNCE_MS = 250;
+
+// Turn a delay in milliseconds into the Duration json repr. for the backend.
+function tickDelay(delay_ms) {
+    const delay_secs = Math.trunc(delay_ms / 1000);
+    return {
+        secs: delay_secs,
+        nanos: (delay_ms - (delay_secs * 1000)) * 1000000
+    };
+}
+
+// Render a Stats message as a single line of text.
+function formatStats(stats) {
+    const ms = (stats.duration.secs * 1000 + stats.duration.nanos / 1000000).toFixed(2);
+    return `generation ${stats.generation} | population ${stats.population}`
+        + ` (+${stats.births} -${stats.deaths}) | ${ms}ms`;
+}
+
 function CMD(name, f = null) {
     if (!f)
         return () => name;
@@ -43,13 +47,19 @@ function CMD(name, f = null) {
 const CMD_MAP = Object.freeze({
     ping: CMD('Ping'),
     step: CMD('Step'),
+    stepBack: CMD('StepBack', (n = 1) => parseInt(n)),
     play: CMD('Play'),
     pause: CMD('Pause'),
     toggle: CMD('Toggle'),
     scroll: CMD('Scroll', (dx, dy) => [parseInt(dx), parseInt(dy)]),
     center: CMD('Center'),
     newGrid: CMD('NewGrid', (grid) => grid),
-    restart: CMD('Restart')
+    soup: CMD('Soup', (spec) => spec),
+    updateSettings: CMD('UpdateSettings', (settings) => settings),
+    resize: CMD('Resize', (width, height) => [parseInt(width), parseInt(height)]),
+    restart: CMD('Restart'),
+    save: CMD('Save'),
+    load: CMD('Load', (id) => id)
 });
 
 const KEYBOARD_SHORTCUTS = Object.freeze({
@@ -60,6 +70,7 @@ const KEYBOARD_SHORTCUTS = Object.freeze({
     'ArrowLeft': (client) => client.send(CMD_MAP.scroll(5, 0)),
     'ArrowRight': (client) => client.send(CMD_MAP.scroll(-5, 0)),
     'c': (client) => client.send(CMD_MAP.center()),
+    'b': (client) => client.send(CMD_MAP.stepBack()),
 });
 
 function StatusBox() {
@@ -87,60 +98,89 @@ function StatusBox() {
     });
 }
 
+// Return the websocket URL given by the server's config, or else build one from the page's host.
+function websocketUrl(config) {
+    if (config.websocket_url)
+        return config.websocket_url;
+    const scheme = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
+    return `${scheme}//${window.location.hostname}:${config.websocket_port}`;
+}
+
 function GameClient(spec) {
-    let { status, $grid } = spec,
-        $socket = new WebSocket(WEBSOCKET_URL),
+    let { status, $grid, $stats, gameId, url } = spec,
+        $socket = null,
+        // Token for the server-side session, used to resume the game after reconnecting.
+        sessionToken = null,
         send = function(msg) {
             return $socket.send(JSON.stringify(msg));
         },
         connected = function() {
             return $socket.readyState === $socket.OPEN;
         },
-        reconnect = function() {
-            $socket = new WebSocket(WEBSOCKET_URL);
-        };
+        connect = function() {
+            const socketUrl = new URL(url);
+            if (sessionToken)
+                socketUrl.searchParams.set('session', sessionToken);
+            $socket = new WebSocket(socketUrl.href);
 
-    Object.assign($socket, {
-        onclose() {
-            status.add('Disconnected from game server.');
-        },
-        onerror(error) {
-            console.log('Error communicating with game server: ' + error);
-        },
-        onmessage(event) {
-            let then = Date.now(),
-                delayMs = 500,
-                messages = JSON.parse(event.data);
-
-            messages.forEach(function(msg) {
-                switch (msg.kind) {
-                case MSG_CONNECTED:
-                    status.add('Connected to game server.');
-                    break;
-                case MSG_STATUS:
-                    status.add(msg.content);
-                    break;
-                case MSG_GRID:
-                    $grid.innerHTML = msg.content.trim();
-                    break;
-                case MSG_ERROR:
-                    status.add('Error: ' + msg.content);
-                    break;
+            Object.assign($socket, {
+                onclose() {
+                    status.add('Disconnected from game server.');
+                },
+                onerror(error) {
+                    console.log('Error communicating with game server: ' + error);
+                },
+                onmessage(event) {
+                    let then = Date.now(),
+                        delayMs = 500,
+                        messages = JSON.parse(event.data);
+
+                    messages.forEach(function(msg) {
+                        switch (msg.kind) {
+                        case MSG_CONNECTED:
+                            status.add('Connected to game server.');
+                            sessionToken = msg.content;
+                            // Load the saved game named in the URL, if any, but only the first time.
+                            if (gameId) {
+                                send(CMD_MAP.load(gameId));
+                                gameId = null;
+                            }
+                            break;
+                        case MSG_STATUS:
+                            status.add(msg.content);
+                            break;
+                        case MSG_GRID:
+                            $grid.innerHTML = msg.content.trim();
+                            break;
+                        case MSG_STATS:
+                            $stats.textContent = formatStats(msg.content);
+                            break;
+                        case MSG_SAVED:
+                            status.add(`Saved game. Share it with: ${window.location.origin}/?game=${msg.content}`);
+                            break;
+                        case MSG_ERROR:
+                            status.add('Error: ' + msg.content.message);
+                            (msg.content.errors || []).slice(1).forEach((err) =>
+                                status.add(`  line ${err.line}, column ${err.column}: ${err.message}`));
+                            break;
+                        }
+                    });
+
+                    delayMs -= (Date.now() - then);
+                    setTimeout(function() {
+                        if (connected())
+                            send(CMD_MAP.ping());
+                    }, delayMs);
                 }
             });
+        };
 
-            delayMs -= (Date.now() - then);
-            setTimeout(function() {
-                send(CMD_MAP.ping());
-            }, delayMs);
-        }
-    });
+    connect();
 
     return Object.freeze({
-        $socket,
         send,
         connected,
-        reconnect
+        reconnect: connect
     });
 }
 
@@ -151,23 +191,49 @@ window.onload = function() {
     const $gameArea = document.getElementById('game-area');
     const $gridForm = document.getElementById('grid-form');
     const $gridField = document.getElementById('grid-field');
-    $gridField.innerHTML = DEFAULT_PATTERN.trim();
+    fetch(`${PATTERNS_URL}/${DEFAULT_PATTERN_NAME}`)
+        .then((response) => response.json())
+        .then((entry) => {
+            $gridField.innerHTML = entry.source.trim();
+        })
+        .catch((error) => console.log('Error loading default pattern: ' + error));
     const $reconnectBtn = document.getElementById('reconnect-btn');
+    const $tickDelay = document.getElementById('tick-delay');
     const $grid = document.getElementById('grid-area');
+    const $stats = document.getElementById('stats');
     const status = StatusBox();
 
+    // Compute width and height to fit containing element.
+    function gridSize() {
+        const fontSize = parseFloat(getComputedStyle($grid).getPropertyValue('font-size'));
+        return [
+            Math.ceil($grid.clientWidth / (fontSize * 0.61)),
+            Math.ceil($grid.clientHeight / (fontSize * 0.51)),
+        ];
+    }
+
   /*
    * Setup WebSocket.
    */
-    let client = GameClient({ status, $grid });
+    const gameId = new URLSearchParams(window.location.search).get('game');
+    let client = null;
+    const connected = () => client !== null && client.connected();
+
+    fetch(CONFIG_URL)
+        .then((response) => response.json())
+        .then((config) => {
+            client = GameClient({ status, $grid, $stats, gameId, url: websocketUrl(config) });
+        })
+        .catch((error) => status.add('Failed to load server config: ' + error));
 
   /*
    * Setup grid form.
    */
     $gridForm.onsubmit = function(event) {
-        if (!client.connected()) {
+        if (!connected()) {
             status.add('Disconnected from game server. Reconnecting...');
-            client.reconnect();
+            if (client)
+                client.reconnect();
             return;
         }
 
@@ -179,40 +245,62 @@ window.onload = function() {
 
         // Build the Settings object.
         const fields = event.target.elements;
-
-        // Fetch `delay` from form and turn it into Duration json repr. for the backend.
-        const delay_ms = fields['tick-delay'].value;
-        const delay_secs = Math.trunc(delay_ms / 1000);
-        const delay_nanos = (delay_ms - (delay_secs * 1000)) * 1000000;
-
-        // Compute width and height to fit containing element.
-        const fontSize = parseFloat(getComputedStyle($grid).getPropertyValue('font-size'));
-        const width = Math.ceil($grid.clientWidth / (fontSize * 0.61));
-        const height = Math.ceil($grid.clientHeight / (fontSize * 0.51));
-
+        const [width, height] = gridSize();
         const settings = Object.assign({
-            delay: {
-                secs: delay_secs,
-                nanos: delay_nanos
-            }
+            delay: tickDelay(fields['tick-delay'].value)
         }, DEFAULT_SETTINGS);
 
         // Send message.
+        // Accept the game's own output as well as the standard pattern format.
         const payload = {
             pattern: $gridField.value,
+            alphabet: {
+                alive: 'x' + DEFAULT_SETTINGS.char_alive,
+                dead: '.' + DEFAULT_SETTINGS.char_dead,
+                comment: '#'
+            },
             settings: settings,
             bounds: [width, height],
         };
         client.send(CMD_MAP.newGrid(payload));
     };
 
+  /*
+   * Update the running game when the tick delay or the window size changes.
+   */
+    $tickDelay.onchange = function() {
+        if (connected())
+            client.send(CMD_MAP.updateSettings({ delay: tickDelay($tickDelay.value) }));
+    };
+
+    let resizeTimer = null;
+    window.addEventListener('resize', function() {
+        clearTimeout(resizeTimer);
+        resizeTimer = setTimeout(function() {
+            if (connected())
+                client.send(CMD_MAP.resize(...gridSize()));
+        }, RESIZE_DEBOUNCE_MS);
+    });
+
+  /*
+   * Random soup button
+   */
+    const $soupField = document.getElementById('soup-field');
+    document.getElementById('soup-btn').onclick = function() {
+        if (!connected()) {
+            status.add('Disconnected from the game server. Start a new game to reconnect.');
+            return;
+        }
+        client.send(CMD_MAP.soup($soupField.value.trim()));
+    };
+
   /*
    * Reconnect button
    */
     $reconnectBtn.onclick = function() {
-        if (client.connected())
+        if (connected())
             status.add('Already connected to game server.');
-        else
+        else if (client)
             client.reconnect();
     };
 
@@ -221,8 +309,10 @@ window.onload = function() {
    */
     document.querySelectorAll('#control-panel button').forEach(function(button) {
         button.onclick = function(event) {
-            if (!client.connected())
+            if (!connected()) {
                 status.add('Disconnected from the game server. Start a new game to reconnect.');
+                return;
+            }
 
             let [name, params] = event.target.value.split(':', 2),
                 makeCmd = CMD_MAP[name],
@@ -248,7 +338,7 @@ window.onload = function() {
         const handleKey = KEYBOARD_SHORTCUTS[event.key];
         if (handleKey) {
             event.preventDefault();
-            if (client.connected())
+            if (connected())
                 handleKey(client);
         }
     });
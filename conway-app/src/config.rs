@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use rocket::config::{Config, ConfigError};
+
+const DEFAULT_WEBSOCKET_PORT: u16 = 3012;
+const DEFAULT_SESSION_GRACE_SECS: u64 = 300;
+
+/// Server settings that Rocket doesn't know about, read from the extras in Rocket's config.
+///
+/// Like Rocket's own settings, these can be given in `Rocket.toml` or as `ROCKET_<KEY>`
+/// environment variables:
+///
+/// - `websocket_address`: address to bind the websocket server to (defaults to `address`)
+/// - `websocket_port`: port to bind the websocket server to (defaults to 3012)
+/// - `websocket_url`: public URL that browsers should connect to, for when the websocket server
+///   is behind a proxy (defaults to the page's host with `websocket_port`)
+/// - `session_grace_secs`: how long to keep a game alive after its client disconnects, so that
+///   the client can resume it (defaults to 300)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub websocket_address: String,
+    pub websocket_port: u16,
+    pub websocket_url: Option<String>,
+    pub session_grace: Duration,
+}
+
+/// The part of the ServerConfig that the browser client needs.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientConfig {
+    pub websocket_port: u16,
+    pub websocket_url: Option<String>,
+}
+
+impl ServerConfig {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let websocket_address = optional(config.get_string("websocket_address"))?
+            .unwrap_or_else(|| config.address.clone());
+        let websocket_port = match optional(config.get_int("websocket_port"))? {
+            Some(port) if port > 0 && port <= i64::from(u16::max_value()) => port as u16,
+            Some(port) => return Err(format!("invalid websocket_port: {}", port)),
+            None => DEFAULT_WEBSOCKET_PORT,
+        };
+        let websocket_url = optional(config.get_string("websocket_url"))?;
+        let session_grace = match optional(config.get_int("session_grace_secs"))? {
+            Some(secs) if secs >= 0 => Duration::from_secs(secs as u64),
+            Some(secs) => return Err(format!("invalid session_grace_secs: {}", secs)),
+            None => Duration::from_secs(DEFAULT_SESSION_GRACE_SECS),
+        };
+
+        Ok(ServerConfig {
+            websocket_address,
+            websocket_port,
+            websocket_url,
+            session_grace,
+        })
+    }
+
+    /// Return the `host:port` address the websocket server should listen on.
+    pub fn websocket_addr(&self) -> String {
+        format!("{}:{}", self.websocket_address, self.websocket_port)
+    }
+
+    pub fn client_config(&self) -> ClientConfig {
+        ClientConfig {
+            websocket_port: self.websocket_port,
+            websocket_url: self.websocket_url.clone(),
+        }
+    }
+}
+
+// Treat a missing key as `None`, but still report keys that are present with the wrong type.
+fn optional<T>(result: Result<T, ConfigError>) -> Result<Option<T>, String> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ConfigError::Missing(_)) => Ok(None),
+        Err(err) => Err(err.to_string()),
+    }
+}
@@ -1,15 +1,47 @@
+use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use rocket;
-use rocket::response::NamedFile;
+use rocket::http::{ContentType, Status};
+use rocket::request::Form;
+use rocket::response::content::Content;
+use rocket::response::status::{Created, Custom};
+use rocket::response::{NamedFile, Response};
+use rocket::{Data, State};
+use rocket_contrib::json::Json;
+
+use conway;
+use conway::config::Settings;
+use conway::{Animation, AnimationOptions, Color, Game, Grid, ImageOptions, Region};
+
+use config::{ClientConfig, ServerConfig};
+use library::{PatternEntry, PatternInfo, PatternLibrary, UploadError};
+use limits::Limits;
+use store::{GameStore, SavedGame};
 
 lazy_static! {
     static ref DIST_DIR: &'static Path = Path::new("client/dist/");
     static ref VENDOR_DIR: &'static Path = Path::new("client/vendor/");
 }
 
-pub fn server() -> rocket::Rocket {
-    rocket::ignite().mount("/", routes![route_index, route_static, route_vendor])
+pub fn server(store: Arc<GameStore>, library: Arc<PatternLibrary>) -> rocket::Rocket {
+    rocket::ignite().manage(library).manage(store).mount(
+        "/",
+        routes![
+            route_index,
+            route_static,
+            route_vendor,
+            route_config,
+            route_list_patterns,
+            route_get_pattern,
+            route_upload_pattern,
+            route_get_game,
+            route_render_png,
+            route_render_gif,
+        ],
+    )
 }
 
 #[get("/", format = "text/html")]
@@ -26,3 +58,259 @@ fn route_static(file: PathBuf) -> Option<NamedFile> {
 fn route_vendor(file: PathBuf) -> Option<NamedFile> {
     NamedFile::open(VENDOR_DIR.join(file)).ok()
 }
+
+/// Return the settings the browser client needs to connect to the server.
+#[get("/api/config")]
+fn route_config(config: State<ServerConfig>) -> Json<ClientConfig> {
+    Json(config.client_config())
+}
+
+/// List patterns in the library, optionally filtered by a name query and/or a tag.
+#[get("/api/patterns?<q>&<tag>")]
+fn route_list_patterns(
+    q: Option<String>,
+    tag: Option<String>,
+    library: State<Arc<PatternLibrary>>,
+) -> Json<Vec<PatternInfo>> {
+    Json(library.search(q.as_ref().map(String::as_str), tag.as_ref().map(String::as_str)))
+}
+
+#[get("/api/patterns/<name>")]
+fn route_get_pattern(
+    name: String,
+    library: State<Arc<PatternLibrary>>,
+) -> Option<Json<PatternEntry>> {
+    library.get(&name).map(Json)
+}
+
+/// Add a new pattern to the library. The request body is the pattern source.
+#[post("/api/patterns/<name>", data = "<data>")]
+fn route_upload_pattern(
+    name: String,
+    data: Data,
+    library: State<Arc<PatternLibrary>>,
+    limits: State<Arc<Limits>>,
+) -> Result<Created<Json<PatternInfo>>, Custom<String>> {
+    let max_bytes = limits.max_pattern_bytes;
+    let mut source = String::new();
+    data.open()
+        .take(max_bytes as u64 + 1)
+        .read_to_string(&mut source)
+        .map_err(|err| Custom(Status::BadRequest, err.to_string()))?;
+    if source.len() > max_bytes {
+        return Err(Custom(
+            Status::PayloadTooLarge,
+            format!("pattern is too large: the limit is {} bytes", max_bytes),
+        ));
+    }
+
+    match library.insert(&name, source) {
+        Ok(info) => Ok(Created(format!("/api/patterns/{}", name), Some(Json(info)))),
+        Err(err) => {
+            let status = match err {
+                UploadError::InvalidName(_) | UploadError::Invalid(_) => Status::BadRequest,
+                UploadError::Exists(_) | UploadError::Duplicate(_) => Status::Conflict,
+                UploadError::IO(_) => Status::InternalServerError,
+            };
+            Err(Custom(status, err.to_string()))
+        }
+    }
+}
+
+/// Fetch a saved game. To resume it in the browser, open `/?game=<id>` instead.
+#[get("/api/games/<id>")]
+fn route_get_game(
+    id: String,
+    store: State<Arc<GameStore>>,
+) -> Result<Option<Json<SavedGame>>, Custom<String>> {
+    store.load(&id).map(|game| game.map(Json)).map_err(|err| {
+        error!("Failed to load game {}: {}", id, err);
+        Custom(Status::InternalServerError, format!("failed to load game '{}'", id))
+    })
+}
+
+/// Which game to render, and how. Exactly one of `pattern` and `game` must be given.
+#[derive(Debug, FromForm)]
+struct RenderQuery {
+    /// The name of a pattern in the library.
+    pattern: Option<String>,
+    /// The id of a saved game.
+    game: Option<String>,
+    /// The generation to draw or to start the animation at, if not the pattern's first or the
+    /// saved game's current one.
+    generation: Option<u64>,
+    region: Option<String>,
+    cell_size: Option<u32>,
+    gridlines: Option<bool>,
+    alive: Option<String>,
+    dead: Option<String>,
+    gridline: Option<String>,
+    /// For animations, the number of frames, the generations between them and the delay
+    /// between them in milliseconds.
+    frames: Option<u64>,
+    frame_step: Option<u64>,
+    frame_delay: Option<u64>,
+}
+
+impl RenderQuery {
+    fn image_options(&self) -> Result<ImageOptions, String> {
+        let defaults = ImageOptions::default();
+        let color = |value: &Option<String>, default: Color| match *value {
+            Some(ref color) => color.parse().map_err(|err: conway::Error| err.to_string()),
+            None => Ok(default),
+        };
+        let options = ImageOptions {
+            cell_size: self.cell_size.unwrap_or(defaults.cell_size),
+            gridlines: self.gridlines.unwrap_or(defaults.gridlines),
+            alive: color(&self.alive, defaults.alive)?,
+            dead: color(&self.dead, defaults.dead)?,
+            gridline: color(&self.gridline, defaults.gridline)?,
+            region: match self.region {
+                Some(ref region) => region
+                    .parse()
+                    .map_err(|err: conway::Error| err.to_string())?,
+                None => Region::Pattern,
+            },
+        };
+        options.validate().map_err(|err| err.to_string())?;
+        Ok(options)
+    }
+
+    fn animation_options(&self) -> Result<AnimationOptions, String> {
+        let defaults = AnimationOptions::default();
+        let options = AnimationOptions {
+            image: self.image_options()?,
+            start: self.generation,
+            frames: self.frames.unwrap_or(defaults.frames),
+            step: self.frame_step.unwrap_or(defaults.step),
+            frame_delay: self
+                .frame_delay
+                .map_or(defaults.frame_delay, Duration::from_millis),
+        };
+        options.validate().map_err(|err| err.to_string())?;
+        Ok(options)
+    }
+
+    /// Return the name of the pattern or saved game, for naming a download.
+    fn name(&self) -> &str {
+        self.pattern
+            .as_ref()
+            .or_else(|| self.game.as_ref())
+            .map_or("game", String::as_str)
+    }
+
+    /// Load the pattern or saved game to render.
+    fn load_game(
+        &self,
+        library: &PatternLibrary,
+        store: &GameStore,
+    ) -> Result<Game, Custom<String>> {
+        let bad_request = |err: String| Custom(Status::BadRequest, err);
+        let not_found =
+            |what: &str, name: &str| Custom(Status::NotFound, format!("no {} '{}'", what, name));
+        match (&self.pattern, &self.game) {
+            (Some(name), None) => {
+                let entry = library
+                    .get(name)
+                    .ok_or_else(|| not_found("pattern", name))?;
+                let grid: Grid = entry
+                    .source
+                    .parse()
+                    .map_err(|err: conway::Error| bad_request(err.to_string()))?;
+                Ok(Game::new(grid, Settings::default(), (None, None)))
+            }
+            (None, Some(id)) => match store.load(id) {
+                Ok(Some(saved)) => Ok(saved.into_games().0),
+                Ok(None) => Err(not_found("saved game", id)),
+                Err(err) => {
+                    error!("Failed to load game {}: {}", id, err);
+                    Err(Custom(
+                        Status::InternalServerError,
+                        format!("failed to load game '{}'", id),
+                    ))
+                }
+            },
+            _ => Err(bad_request(
+                "give either a pattern or a saved game to render".to_string(),
+            )),
+        }
+    }
+}
+
+/// Draw a pattern from the library or a saved game as a PNG image, e.g.
+/// `/api/render.png?pattern=glider&generation=4&cell_size=16&gridlines=true`.
+#[get("/api/render.png?<query..>")]
+fn route_render_png(
+    query: Form<RenderQuery>,
+    library: State<Arc<PatternLibrary>>,
+    store: State<Arc<GameStore>>,
+    limits: State<Arc<Limits>>,
+) -> Result<Content<Vec<u8>>, Custom<String>> {
+    let bad_request = |err: String| Custom(Status::BadRequest, err);
+    let options = query.image_options().map_err(bad_request)?;
+    let mut game = query.load_game(&library, &store)?;
+    if let Some(generation) = query.generation {
+        limits.check_generation(generation).map_err(bad_request)?;
+        game.seek(generation)
+            .map_err(|err| bad_request(err.to_string()))?;
+    }
+    let bounds = options.region.bounds(game.grid(), Some(game.viewport()));
+    let (width, height) = options.image_size(bounds);
+    limits.check_image(width, height).map_err(bad_request)?;
+
+    let mut png = Vec::new();
+    game.to_image(&options)
+        .and_then(|image| image.write_png(&mut png))
+        .map_err(|err| Custom(Status::InternalServerError, err.to_string()))?;
+    Ok(Content(ContentType::PNG, png))
+}
+
+/// Download an animated GIF of a run of a pattern from the library or a saved game, e.g.
+/// `/api/render.gif?pattern=glider&frames=40&frame_step=2&frame_delay=50`.
+#[get("/api/render.gif?<query..>")]
+fn route_render_gif(
+    query: Form<RenderQuery>,
+    library: State<Arc<PatternLibrary>>,
+    store: State<Arc<GameStore>>,
+    limits: State<Arc<Limits>>,
+) -> Result<Response<'static>, Custom<String>> {
+    let bad_request = |err: String| Custom(Status::BadRequest, err);
+    let options = query.animation_options().map_err(bad_request)?;
+    limits.check_frames(options.frames).map_err(bad_request)?;
+    let mut game = query.load_game(&library, &store)?;
+    let start = options.start.unwrap_or_else(|| game.generation());
+    limits
+        .check_generation(options.end(start))
+        .map_err(bad_request)?;
+    // Recording runs the whole simulation, so check the size first unless the frame grows to fit
+    // the pattern, in which case it isn't known until afterwards.
+    if options.image.region != Region::Pattern {
+        let bounds = options
+            .image
+            .region
+            .bounds(game.grid(), Some(game.viewport()));
+        let (width, height) = options.image.image_size(bounds);
+        limits
+            .check_animation(width, height, options.frames)
+            .map_err(bad_request)?;
+    }
+
+    let animation =
+        Animation::record(&mut game, &options).map_err(|err| bad_request(err.to_string()))?;
+    let (width, height) = animation.size();
+    limits
+        .check_animation(width, height, animation.len() as u64)
+        .map_err(bad_request)?;
+    let mut gif = Vec::new();
+    animation
+        .write_gif(&mut gif)
+        .map_err(|err| Custom(Status::InternalServerError, err.to_string()))?;
+    Ok(Response::build()
+        .header(ContentType::GIF)
+        .raw_header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}.gif\"", query.name()),
+        )
+        .sized_body(Cursor::new(gif))
+        .finalize())
+}
@@ -9,9 +9,15 @@ extern crate serde_derive;
 extern crate conway;
 #[macro_use]
 extern crate rocket;
+extern crate rocket_contrib;
 extern crate serde;
 extern crate serde_json;
 extern crate ws;
 
+pub mod config;
 pub mod http;
+pub mod library;
+pub mod limits;
 pub mod pubsub;
+pub mod sessions;
+pub mod store;
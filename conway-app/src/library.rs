@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use conway::{Error, ErrorKind, LocalResolver, Pattern, PatternResolver, PatternSource};
+
+/// Metadata from the comment header at the top of a pattern file.
+///
+/// A header looks like this:
+///
+/// ```text
+/// # Glider
+/// # Type: Spaceship
+/// # Tags: small, c/4
+/// ```
+///
+/// The title is the Pattern's name. The `Type` value is also treated as a tag, so searching for
+/// the tag "spaceship" will find the pattern above.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PatternInfo {
+    pub name: String,
+    pub title: Option<String>,
+    pub kind: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl PatternInfo {
+    /// Create a PatternInfo for the pattern with the given name.
+    pub fn new(name: &str, pattern: &Pattern) -> Self {
+        let mut info = PatternInfo {
+            name: name.to_string(),
+            title: pattern.name.clone(),
+            kind: pattern.field("type").map(str::to_string),
+            ..Default::default()
+        };
+        if let Some(kind) = pattern.field("type") {
+            info.add_tag(kind);
+        }
+        if let Some(tags) = pattern.field("tags") {
+            for tag in tags.split(',') {
+                info.add_tag(tag);
+            }
+        }
+        info
+    }
+
+    /// Return whether the pattern has the given tag (case-insensitive).
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim().to_lowercase();
+        self.tags.iter().any(|t| *t == tag)
+    }
+
+    /// Return whether the name or title contains `query` (case-insensitive).
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        self.name.to_lowercase().contains(&query)
+            || self
+                .title
+                .as_ref()
+                .map_or(false, |title| title.to_lowercase().contains(&query))
+    }
+
+    fn add_tag(&mut self, tag: &str) {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+    }
+}
+
+/// A pattern in the library, along with its source text.
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternEntry {
+    #[serde(flatten)]
+    pub info: PatternInfo,
+    pub source: String,
+    /// The content hash of the pattern, used to detect duplicates.
+    #[serde(skip)]
+    pub hash: u64,
+}
+
+#[derive(Debug)]
+pub enum UploadError {
+    InvalidName(String),
+    Exists(String),
+    /// The same pattern, in any position or orientation, is already in the library.
+    Duplicate(String),
+    Invalid(Error),
+    IO(io::Error),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UploadError::InvalidName(name) => write!(
+                f,
+                "invalid pattern name '{}': expected letters, digits, '-' or '_'",
+                name
+            ),
+            UploadError::Exists(name) => write!(f, "pattern '{}' already exists", name),
+            UploadError::Duplicate(name) => {
+                write!(f, "the same pattern already exists as '{}'", name)
+            }
+            UploadError::Invalid(err) => write!(f, "invalid pattern: {}", err),
+            UploadError::IO(err) => write!(f, "failed to store pattern: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for UploadError {
+    fn from(err: io::Error) -> Self {
+        UploadError::IO(err)
+    }
+}
+
+/// A PatternLibrary is a directory of pattern files, indexed by file name.
+pub struct PatternLibrary {
+    dir: PathBuf,
+    entries: RwLock<BTreeMap<String, PatternEntry>>,
+}
+
+impl PatternLibrary {
+    /// Load every valid pattern file in `dir`. Files that can't be read as text or fail to parse
+    /// are skipped.
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        let mut entries = BTreeMap::new();
+
+        for file in fs::read_dir(&dir)? {
+            let path = file?.path();
+            let name = match path.file_name().and_then(|s| s.to_str()) {
+                Some(name) if path.is_file() && is_valid_name(name) => name.to_string(),
+                _ => continue,
+            };
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(err) => {
+                    warn!("Skipping unreadable pattern {:?}: {}", path, err);
+                    continue;
+                }
+            };
+            let pattern = match source.parse::<Pattern>() {
+                Ok(pattern) => pattern,
+                Err(err) => {
+                    warn!("Skipping invalid pattern {:?}: {}", path, err);
+                    continue;
+                }
+            };
+            entries.insert(
+                name.clone(),
+                PatternEntry {
+                    info: PatternInfo::new(&name, &pattern),
+                    source,
+                    hash: pattern.grid.content_hash(),
+                },
+            );
+        }
+
+        Ok(PatternLibrary {
+            dir,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// Return the pattern with the given name.
+    pub fn get(&self, name: &str) -> Option<PatternEntry> {
+        self.entries.read().unwrap().get(name).cloned()
+    }
+
+    /// Return metadata for every pattern matching `query` and `tag`, sorted by name.
+    ///
+    /// A `None` filter matches everything.
+    pub fn search(&self, query: Option<&str>, tag: Option<&str>) -> Vec<PatternInfo> {
+        self.entries
+            .read()
+            .unwrap()
+            .values()
+            .map(|entry| &entry.info)
+            .filter(|info| query.map_or(true, |q| info.matches(q)))
+            .filter(|info| tag.map_or(true, |t| info.has_tag(t)))
+            .cloned()
+            .collect()
+    }
+
+    /// Validate a new pattern, write it to the library directory and add it to the index.
+    ///
+    /// Patterns that are already in the library under another name are rejected.
+    pub fn insert(&self, name: &str, source: String) -> Result<PatternInfo, UploadError> {
+        if !is_valid_name(name) {
+            return Err(UploadError::InvalidName(name.to_string()));
+        }
+        let pattern: Pattern = source.parse().map_err(UploadError::Invalid)?;
+        let hash = pattern.grid.content_hash();
+
+        let mut entries = self.entries.write().unwrap();
+        if entries.contains_key(name) {
+            return Err(UploadError::Exists(name.to_string()));
+        }
+        if let Some(existing) = entries.values().find(|entry| entry.hash == hash) {
+            return Err(UploadError::Duplicate(existing.info.name.clone()));
+        }
+        fs::write(self.dir.join(name), &source)?;
+
+        let info = PatternInfo::new(name, &pattern);
+        entries.insert(
+            name.to_string(),
+            PatternEntry {
+                info: info.clone(),
+                source,
+                hash,
+            },
+        );
+        Ok(info)
+    }
+}
+
+/// Patterns in a scene can come from the library, samples or inline text. Files are refused, so
+/// that clients can't read from the server's file system.
+impl PatternResolver for PatternLibrary {
+    fn resolve(&self, source: &PatternSource) -> conway::Result<String> {
+        match source {
+            PatternSource::Library(name) => match self.get(name) {
+                Some(entry) => Ok(entry.source),
+                None => Err(ErrorKind::InvalidConfig(
+                    "scene",
+                    format!("unknown pattern '{}'", name),
+                )
+                .into()),
+            },
+            PatternSource::File(_) => Err(ErrorKind::InvalidConfig(
+                "scene",
+                "pattern files can't be loaded on the server".to_string(),
+            )
+            .into()),
+            PatternSource::Inline(_) | PatternSource::Sample(_) => LocalResolver.resolve(source),
+        }
+    }
+}
+
+// Pattern names double as file names, so only allow characters that are safe in a path.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
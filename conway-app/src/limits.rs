@@ -0,0 +1,376 @@
+use std::time::Instant;
+
+use rocket::config::{Config, ConfigError, Value};
+
+use conway::{Game, GameConfig, History, PatternSource, Point};
+
+/// The name of the table in `Rocket.toml` that Limits are read from.
+const CONFIG_KEY: &str = "game_limits";
+
+/// Limits on the resources that a single client can make the server use.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Maximum viewport width, in cells.
+    pub max_width: u64,
+    /// Maximum viewport height, in cells.
+    pub max_height: u64,
+    /// Maximum size of a pattern's source text, in bytes.
+    pub max_pattern_bytes: usize,
+    /// Maximum number of live cells in a new pattern.
+    pub max_pattern_cells: usize,
+    /// Maximum number of patterns placed in a scene.
+    pub max_placements: usize,
+    /// Maximum distance from the origin, in cells, of a placement in a scene or a live cell in a
+    /// new pattern.
+    pub max_coordinate: u64,
+    /// Maximum number of live cells in a running game. Games that grow past this are paused.
+    pub max_population: usize,
+    /// Maximum number of live cells kept in a game's history, for stepping back.
+    pub max_history_cells: usize,
+    /// Maximum size of a websocket message, in bytes.
+    pub max_message_bytes: usize,
+    /// Maximum number of commands per second on a single connection.
+    pub max_commands_per_sec: u32,
+    /// Maximum number of concurrent websocket connections.
+    pub max_sessions: usize,
+    /// Maximum size of a rendered image, in pixels.
+    pub max_image_pixels: u64,
+    /// Maximum generation that a pattern is run to before rendering it.
+    pub max_render_generation: u64,
+    /// Maximum number of frames in a rendered animation.
+    pub max_animation_frames: u64,
+    /// Maximum size of a rendered animation, in pixels across all of its frames.
+    pub max_animation_pixels: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_width: 500,
+            max_height: 500,
+            max_pattern_bytes: 64 * 1024,
+            max_pattern_cells: 10_000,
+            max_placements: 64,
+            max_coordinate: 1_000_000,
+            max_population: 100_000,
+            max_history_cells: 200_000,
+            max_message_bytes: 128 * 1024,
+            max_commands_per_sec: 20,
+            max_sessions: 100,
+            max_image_pixels: 4_000_000,
+            max_render_generation: 1000,
+            max_animation_frames: 200,
+            max_animation_pixels: 50_000_000,
+        }
+    }
+}
+
+impl Limits {
+    /// Read Limits from the `[<env>.game_limits]` table in Rocket's config, falling back to the
+    /// defaults for anything not given.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        match config.get_table(CONFIG_KEY) {
+            Ok(table) => Value::Table(table.clone())
+                .try_into()
+                .map_err(|err| format!("invalid value in '{}': {}", CONFIG_KEY, err)),
+            Err(ConfigError::Missing(_)) => Ok(Limits::default()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Check a GameConfig before it is built.
+    pub fn check_config(&self, config: &GameConfig) -> Result<(), String> {
+        if config.scene.len() > self.max_placements {
+            return Err(format!(
+                "scene has too many patterns: the limit is {}",
+                self.max_placements
+            ));
+        }
+        // Offsets are checked here because the scene can't even be built if they're extreme
+        // enough to overflow.
+        for placement in &config.scene {
+            self.check_point("placement", placement.offset)?;
+        }
+        // Inline patterns in the scene count towards the size of the pattern.
+        let inline_bytes: usize = config
+            .scene
+            .iter()
+            .map(|placement| match placement.source {
+                PatternSource::Inline(ref pattern) => pattern.len(),
+                _ => 0,
+            })
+            .sum();
+        if config.pattern.len() + inline_bytes > self.max_pattern_bytes {
+            return Err(format!(
+                "pattern is too large: the limit is {} bytes",
+                self.max_pattern_bytes
+            ));
+        }
+        let (width, height) = config.bounds;
+        self.check_size(width.unwrap_or(0), height.unwrap_or(0))
+    }
+
+    /// Check a newly built Game. This also catches viewports that were sized to fit the pattern.
+    pub fn check_game(&self, game: &Game) -> Result<(), String> {
+        self.check_size(game.viewport.width, game.viewport.height)?;
+        if game.grid().len() > self.max_pattern_cells {
+            return Err(format!(
+                "pattern has too many live cells: the limit is {}",
+                self.max_pattern_cells
+            ));
+        }
+        if !game.grid().is_empty() {
+            let (min, max) = game.grid().bounds();
+            self.check_point("pattern", min)?;
+            self.check_point("pattern", max)?;
+        }
+        Ok(())
+    }
+
+    /// Check that a point is within `max_coordinate` of the origin.
+    pub fn check_point(&self, what: &str, point: Point) -> Result<(), String> {
+        let Point(x, y) = point;
+        if x.unsigned_abs() > self.max_coordinate || y.unsigned_abs() > self.max_coordinate {
+            return Err(format!(
+                "{} at {} is too far from the origin: the limit is {} cells",
+                what, point, self.max_coordinate
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the population of a running Game.
+    pub fn check_population(&self, game: &Game) -> Result<(), String> {
+        if game.grid().len() > self.max_population {
+            return Err(format!(
+                "population exceeded the limit of {} live cells; the game has been paused",
+                self.max_population
+            ));
+        }
+        Ok(())
+    }
+
+    /// Return an empty History for a game run on the server, bounded by the history limit.
+    pub fn history(&self) -> History {
+        History::default().with_max_cells(self.max_history_cells)
+    }
+
+    /// Check the size of a viewport.
+    pub fn check_size(&self, width: u64, height: u64) -> Result<(), String> {
+        for &(dimension, size, max) in &[
+            ("width", width, self.max_width),
+            ("height", height, self.max_height),
+        ] {
+            if size > max {
+                return Err(format!(
+                    "viewport {} of {} is too large: the limit is {}",
+                    dimension, size, max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the size of an image to be rendered, in pixels.
+    pub fn check_image(&self, width: u64, height: u64) -> Result<(), String> {
+        if width.saturating_mul(height) > self.max_image_pixels {
+            return Err(format!(
+                "image of {}x{} pixels is too large: the limit is {} pixels",
+                width, height, self.max_image_pixels
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the generation that a pattern is run to before rendering it.
+    pub fn check_generation(&self, generation: u64) -> Result<(), String> {
+        if generation > self.max_render_generation {
+            return Err(format!(
+                "generation {} is too late: the limit is {}",
+                generation, self.max_render_generation
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the number of frames in an animation to be rendered.
+    pub fn check_frames(&self, frames: u64) -> Result<(), String> {
+        if frames > self.max_animation_frames {
+            return Err(format!(
+                "animation of {} frames is too long: the limit is {}",
+                frames, self.max_animation_frames
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the total size of an animation to be rendered, in pixels across all of its frames.
+    pub fn check_animation(&self, width: u64, height: u64, frames: u64) -> Result<(), String> {
+        self.check_image(width, height)?;
+        if width.saturating_mul(height).saturating_mul(frames) > self.max_animation_pixels {
+            return Err(format!(
+                "animation of {} frames of {}x{} pixels is too large: the limit is {} pixels",
+                frames, width, height, self.max_animation_pixels
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A RateLimiter is a token bucket that allows bursts of up to one second's worth of commands.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_sec: u32) -> Self {
+        RateLimiter {
+            rate: f64::from(per_sec),
+            tokens: f64::from(per_sec),
+            last: Instant::now(),
+        }
+    }
+
+    /// Take a token from the bucket, returning false if the bucket is empty.
+    pub fn check(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last);
+        self.last = now;
+        self.tokens = (self.tokens + self.rate * elapsed.as_secs_f64()).min(self.rate);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use conway::config::Settings;
+    use conway::Grid;
+    use std::thread;
+    use std::time::Duration;
+
+    fn mk_config(json: &str) -> GameConfig {
+        GameConfig::from_json(json).unwrap()
+    }
+
+    fn mk_game(cells: Vec<Point>, bounds: (u64, u64)) -> Game {
+        Game::new(
+            Grid::new(cells),
+            Settings::default(),
+            (Some(bounds.0), Some(bounds.1)),
+        )
+    }
+
+    #[test]
+    fn test_check_config() {
+        let limits = Limits {
+            max_placements: 2,
+            max_pattern_bytes: 16,
+            max_coordinate: 100,
+            ..Limits::default()
+        };
+        let ok = r#"{
+            "pattern": "xx",
+            "bounds": [10, 10],
+            "scene": [{"source": {"inline": "xx"}, "offset": [100, -100]}]
+        }"#;
+        assert_eq!(limits.check_config(&mk_config(ok)), Ok(()));
+
+        for json in &[
+            // Too many placements.
+            r#"{"bounds": [null, null], "scene": [
+                {"source": {"sample": "glider"}},
+                {"source": {"sample": "glider"}},
+                {"source": {"sample": "glider"}}
+            ]}"#,
+            // Inline patterns count towards the size of the pattern.
+            r#"{"pattern": "xxxxxxxxxx", "bounds": [null, null], "scene": [
+                {"source": {"inline": "xxxxxxxxxx"}}
+            ]}"#,
+            // Placements too far from the origin, including ones that would overflow.
+            r#"{"bounds": [null, null], "scene": [
+                {"source": {"inline": "xx"}, "offset": [101, 0]}
+            ]}"#,
+            r#"{"bounds": [null, null], "scene": [
+                {"source": {"inline": "xx"}, "offset": [-9223372036854775808, 0]}
+            ]}"#,
+            // A viewport that's too wide.
+            r#"{"pattern": "xx", "bounds": [501, 10]}"#,
+        ] {
+            assert!(limits.check_config(&mk_config(json)).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_check_game() {
+        let limits = Limits {
+            max_pattern_cells: 2,
+            max_population: 3,
+            max_coordinate: 100,
+            ..Limits::default()
+        };
+        let game = mk_game(vec![Point(-100, 0), Point(100, 0)], (10, 10));
+        assert_eq!(limits.check_game(&game), Ok(()));
+        assert_eq!(limits.check_population(&game), Ok(()));
+
+        for game in &[
+            mk_game(vec![Point(0, 0), Point(1, 0), Point(2, 0)], (10, 10)),
+            mk_game(vec![Point(0, 101)], (10, 10)),
+            mk_game(vec![Point(i64::MIN, 0)], (10, 10)),
+            mk_game(vec![Point(0, 0)], (10, 501)),
+        ] {
+            assert!(limits.check_game(game).is_err(), "{:?}", game.grid());
+        }
+        let crowded = mk_game((0..4).map(|x| Point(x, 0)).collect(), (10, 10));
+        assert!(limits.check_population(&crowded).is_err());
+    }
+
+    #[test]
+    fn test_history() {
+        let limits = Limits {
+            max_history_cells: 2,
+            ..Limits::default()
+        };
+        let mut history = limits.history();
+        history.record(0, &Grid::new(vec![Point(0, 0), Point(1, 0), Point(2, 0)]));
+        assert_eq!(history.earliest(), None);
+        history.record(1, &Grid::new(vec![Point(0, 0), Point(1, 0)]));
+        assert_eq!(history.earliest(), Some(1));
+    }
+
+    #[test]
+    fn test_check_render() {
+        let limits = Limits::default();
+        assert_eq!(limits.check_image(2000, 2000), Ok(()));
+        assert!(limits.check_image(2001, 2000).is_err());
+        assert!(limits.check_image(u64::MAX, 2).is_err());
+        assert_eq!(limits.check_generation(1000), Ok(()));
+        assert!(limits.check_generation(1001).is_err());
+        assert_eq!(limits.check_frames(200), Ok(()));
+        assert!(limits.check_frames(201).is_err());
+        assert_eq!(limits.check_animation(500, 500, 200), Ok(()));
+        assert!(limits.check_animation(1000, 1000, 51).is_err());
+        assert!(limits.check_animation(3000, 3000, 1).is_err());
+        assert!(limits.check_animation(1000, 1000, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(2);
+        assert!(limiter.check());
+        assert!(limiter.check());
+        assert!(!limiter.check(), "should only allow a burst");
+        thread::sleep(Duration::from_millis(600));
+        assert!(limiter.check(), "should refill over time");
+        assert!(!limiter.check());
+    }
+}
@@ -1,20 +1,81 @@
 #[macro_use]
+extern crate clap;
+#[macro_use]
 extern crate log;
 extern crate conway;
 extern crate conway_server;
 extern crate env_logger;
 
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::thread;
 
+use clap::ArgMatches;
+
+use conway_server::config::ServerConfig;
+use conway_server::library::PatternLibrary;
+use conway_server::limits::Limits;
+use conway_server::store::GameStore;
 use conway_server::{http, pubsub};
 
-const WEBSOCKET_ADDR: &str = "localhost:3012";
+const SAVE_DIR: &str = "saves/";
+const PATTERN_DIR: &str = "static/patterns/";
+
+/// Command line options, and the Rocket config keys they override.
+const CONFIG_ARGS: &[(&str, &str)] = &[
+    ("address", "ADDRESS"),
+    ("port", "PORT"),
+    ("ws_address", "WEBSOCKET_ADDRESS"),
+    ("ws_port", "WEBSOCKET_PORT"),
+    ("ws_url", "WEBSOCKET_URL"),
+];
+
+fn parse_args<'a>() -> ArgMatches<'a> {
+    clap_app!(("conway-server") =>
+        (version: "0.1")
+        (author: "Dustin Rohde <dustin.rohde@gmail.com>")
+        (about: "A web interface for running Conway's Game of Life simulations.")
+        (@arg address: -a --address +takes_value
+            "address to serve HTTP on")
+        (@arg port: -p --port +takes_value
+            "port to serve HTTP on")
+        (@arg ws_address: --("ws-address") +takes_value
+            "address to serve the websocket on")
+        (@arg ws_port: --("ws-port") +takes_value
+            "port to serve the websocket on")
+        (@arg ws_url: --("ws-url") +takes_value
+            "public websocket URL for browsers, e.g. when behind a proxy")
+    )
+    .get_matches()
+}
 
 fn main() {
     env_logger::init();
-    thread::spawn(|| {
-        pubsub::listen(WEBSOCKET_ADDR).unwrap();
+
+    // Rocket reads `ROCKET_<KEY>` environment variables over `Rocket.toml`, so passing command
+    // line options through the environment gives them the highest precedence of all.
+    let matches = parse_args();
+    for (arg, key) in CONFIG_ARGS {
+        if let Some(value) = matches.value_of(arg) {
+            env::set_var(format!("ROCKET_{}", key), value);
+        }
+    }
+
+    let store = Arc::new(GameStore::open(PathBuf::from(SAVE_DIR)).unwrap());
+    let library = Arc::new(
+        PatternLibrary::open(PathBuf::from(PATTERN_DIR)).expect("failed to load pattern library"),
+    );
+    let server = http::server(store.clone(), library.clone());
+    let limits = Arc::new(Limits::from_config(server.config()).unwrap());
+    let config = ServerConfig::from_config(server.config()).unwrap();
+
+    let ws_addr = config.websocket_addr();
+    let ws_limits = limits.clone();
+    let session_grace = config.session_grace;
+    thread::spawn(move || {
+        pubsub::listen(&ws_addr, store, library, ws_limits, session_grace).unwrap();
     });
-    let err = http::server().launch();
+    let err = server.manage(limits).manage(config).launch();
     error!("Error starting server: {:?}", err);
 }
@@ -1,30 +1,85 @@
 use std::iter::FromIterator;
 use std::ops::{Add, AddAssign};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use serde::Serialize;
-use serde_json;
+use serde_json::{self, Value};
 use ws;
 
-use conway::config::Settings;
-use conway::{Game, GameConfig, View};
+use conway::config::{Settings, SettingsUpdate};
+use conway::{Error, ErrorKind, Game, GameConfig, ParseError, SoupSpec, View};
 
-pub fn listen(addr: &str) -> ws::Result<()> {
-    ws::listen(addr, Server::new)
+use library::PatternLibrary;
+use limits::{Limits, RateLimiter};
+use sessions::SessionStore;
+use store::{GameStore, SavedGame};
+
+/// The query parameter a reconnecting client uses to pass its session token.
+const SESSION_PARAM: &str = "session=";
+
+/// Error codes sent in `ErrorReply`.
+pub const ERR_PARSE: &str = "parse_error";
+pub const ERR_INVALID_CONFIG: &str = "invalid_config";
+pub const ERR_INVALID_COMMAND: &str = "invalid_command";
+pub const ERR_LIMIT_EXCEEDED: &str = "limit_exceeded";
+pub const ERR_RATE_LIMITED: &str = "rate_limited";
+pub const ERR_SERVER_FULL: &str = "server_full";
+pub const ERR_NOT_FOUND: &str = "not_found";
+pub const ERR_INTERNAL: &str = "internal_error";
+
+pub fn listen(
+    addr: &str,
+    store: Arc<GameStore>,
+    library: Arc<PatternLibrary>,
+    limits: Arc<Limits>,
+    session_grace: Duration,
+) -> ws::Result<()> {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let sessions = Arc::new(SessionStore::new(session_grace, limits.max_sessions));
+    let settings = ws::Settings {
+        // Leave some room above the session limit, so that clients over the limit are told why
+        // they're being turned away instead of just being dropped.
+        max_connections: limits.max_sessions + 16,
+        // Hard cap on frame size, so that oversized messages are never buffered in full.
+        // Anything between the soft and hard limits gets a proper error reply.
+        max_fragment_size: limits.max_message_bytes * 2,
+        ..Default::default()
+    };
+    ws::Builder::new()
+        .with_settings(settings)
+        .build(|out| {
+            Server::new(
+                out,
+                store.clone(),
+                library.clone(),
+                limits.clone(),
+                connections.clone(),
+                sessions.clone(),
+            )
+        })?
+        .listen(addr)
+        .map(|_| ())
 }
 
 #[derive(Deserialize)]
 pub enum Cmd {
     Ping,
     Step,
+    StepBack(u64),
     Play,
     Pause,
     Toggle,
     Scroll(i64, i64),
     Center,
     NewGrid(GameConfig),
+    Soup(String),
+    UpdateSettings(SettingsUpdate),
+    Resize(u64, u64),
     Restart,
+    Save,
+    Load(String),
 }
 
 #[derive(Debug, Serialize)]
@@ -33,9 +88,62 @@ pub enum Message<T> {
     Connected(T),
     Status(T),
     Grid(T),
+    Stats(T),
+    Saved(T),
     Error(T),
 }
 
+/// The content of a `Message::Error`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorReply {
+    /// A machine-readable error code, one of the `ERR_*` constants.
+    pub code: &'static str,
+    pub message: String,
+    /// The config field that was invalid, for `invalid_config` errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<&'static str>,
+    /// Where in the pattern the error occurred, for `parse_error` errors (1-based).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// Every error found in the pattern, for `parse_error` errors. `line` and `column` give the
+    /// location of the first one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ParseError>,
+}
+
+impl ErrorReply {
+    pub fn new<S: Into<String>>(code: &'static str, message: S) -> Self {
+        ErrorReply {
+            code,
+            message: message.into(),
+            field: None,
+            line: None,
+            column: None,
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl From<Error> for ErrorReply {
+    fn from(err: Error) -> Self {
+        let mut reply = ErrorReply::new(ERR_INVALID_CONFIG, err.to_string());
+        match err.kind() {
+            ErrorKind::ParseGrid(errors) => {
+                reply.code = ERR_PARSE;
+                reply.line = errors.first().map(|err| err.line);
+                reply.column = errors.first().map(|err| err.column);
+                reply.errors = errors.clone();
+            }
+            ErrorKind::InvalidConfig(field, _) => reply.field = Some(*field),
+            ErrorKind::HistoryUnavailable(_) => reply.code = ERR_NOT_FOUND,
+            _ => (),
+        }
+        reply
+    }
+}
+
 impl<T> Message<T> {
     fn map<U, F>(self, f: F) -> Message<U>
     where
@@ -45,18 +153,20 @@ impl<T> Message<T> {
             Message::Connected(t) => Message::Connected(f(t)),
             Message::Status(t) => Message::Status(f(t)),
             Message::Grid(t) => Message::Grid(f(t)),
+            Message::Stats(t) => Message::Stats(f(t)),
+            Message::Saved(t) => Message::Saved(f(t)),
             Message::Error(t) => Message::Error(f(t)),
         }
     }
 }
 
-impl<T: ToString + Serialize> From<Message<T>> for ws::Message {
+impl<T: Serialize> From<Message<T>> for ws::Message {
     fn from(msg: Message<T>) -> Self {
         ws::Message::Text(serde_json::to_string(&msg).unwrap())
     }
 }
 
-impl<T: ToString> Add for Message<T> {
+impl<T: Serialize> Add for Message<T> {
     type Output = MessageQueue;
 
     fn add(self, msg: Message<T>) -> MessageQueue {
@@ -68,35 +178,41 @@ impl<T: ToString> Add for Message<T> {
 }
 
 #[derive(Debug)]
-pub struct MessageQueue(Vec<Message<String>>);
+pub struct MessageQueue(Vec<Message<Value>>);
 
 impl MessageQueue {
     fn new() -> Self {
         MessageQueue(Vec::new())
     }
 
-    fn push<T: ToString>(&mut self, msg: Message<T>) {
-        self.0.push(msg.map(|s| s.to_string()));
+    fn push<T: Serialize>(&mut self, msg: Message<T>) {
+        self.0.push(msg.map(|t| serde_json::to_value(t).unwrap()));
     }
 
-    fn append<T: ToString>(&mut self, msgs: Vec<Message<T>>) {
+    /// Push the current grid of a Game, followed by its latest Stats.
+    fn push_frame(&mut self, game: &Game) {
+        self.push(Message::Grid(game.draw()));
+        self.push(Message::Stats(game.stats()));
+    }
+
+    fn append<T: Serialize>(&mut self, msgs: Vec<Message<T>>) {
         self.0.append(
             &mut msgs
                 .into_iter()
-                .map(|msg: Message<T>| msg.map(|s: T| s.to_string()))
+                .map(|msg: Message<T>| msg.map(|t: T| serde_json::to_value(t).unwrap()))
                 .collect(),
         )
     }
 
     fn drain<B>(&mut self) -> B
     where
-        B: FromIterator<Message<String>>,
+        B: FromIterator<Message<Value>>,
     {
         FromIterator::from_iter(self.0.drain(..))
     }
 
     fn flush(&mut self, out: &ws::Sender) -> ws::Result<()> {
-        out.send(serde_json::to_string::<Vec<Message<String>>>(&self.drain()).unwrap())
+        out.send(serde_json::to_string::<Vec<Message<Value>>>(&self.drain()).unwrap())
     }
 }
 
@@ -106,7 +222,7 @@ impl<'a> From<&'a MessageQueue> for ws::Message {
     }
 }
 
-impl<T: ToString> Add<Message<T>> for MessageQueue {
+impl<T: Serialize> Add<Message<T>> for MessageQueue {
     type Output = Self;
     fn add(mut self, msg: Message<T>) -> MessageQueue {
         self.push(msg);
@@ -114,26 +230,43 @@ impl<T: ToString> Add<Message<T>> for MessageQueue {
     }
 }
 
-impl<T: ToString> AddAssign<Message<T>> for MessageQueue {
+impl<T: Serialize> AddAssign<Message<T>> for MessageQueue {
     fn add_assign(&mut self, msg: Message<T>) {
         self.push(msg);
     }
 }
 
-struct State {
+/// The state of a game session, which outlives the connection that created it.
+pub struct State {
     game: Game,
+    initial_game: Game,
+    paused: bool,
     queue: MessageQueue,
 }
 
 pub struct Server {
     out: ws::Sender,
+    store: Arc<GameStore>,
+    library: Arc<PatternLibrary>,
+    limits: Arc<Limits>,
+    rate_limiter: RateLimiter,
+    connections: Arc<AtomicUsize>,
+    over_capacity: bool,
+    sessions: Arc<SessionStore<State>>,
+    token: Option<String>,
     state: Arc<Mutex<State>>,
-    initial_game: Game,
-    paused: bool,
 }
 
 impl Server {
-    pub fn new(out: ws::Sender) -> Self {
+    pub fn new(
+        out: ws::Sender,
+        store: Arc<GameStore>,
+        library: Arc<PatternLibrary>,
+        limits: Arc<Limits>,
+        connections: Arc<AtomicUsize>,
+        sessions: Arc<SessionStore<State>>,
+    ) -> Self {
+        let over_capacity = connections.fetch_add(1, Ordering::SeqCst) >= limits.max_sessions;
         let game = Game::new(
             String::new().parse().unwrap(),
             Settings {
@@ -144,24 +277,125 @@ impl Server {
                 ..Default::default()
             },
             (Some(50), Some(50)),
-        );
+        )
+        .with_history(limits.history());
         Server {
             out,
+            store,
+            library,
+            rate_limiter: RateLimiter::new(limits.max_commands_per_sec),
+            limits,
+            connections,
+            over_capacity,
+            sessions,
+            token: None,
             state: Arc::new(Mutex::new(State {
                 queue: MessageQueue::new(),
-                game: game.clone(),
+                initial_game: game.clone(),
+                game,
+                paused: true,
             })),
-            initial_game: game,
-            paused: true,
         }
     }
 
-    fn next_turn(&self, game: &mut Game, queue: &mut MessageQueue) {
+    /// Pick up the session named in the request, or else start a new one.
+    ///
+    /// Returns whether an existing session was resumed, or `None` if there's no room for a new
+    /// one.
+    fn attach(&mut self, resource: &str) -> Option<bool> {
+        let owner = self.out.connection_id();
+        let resumed = resource
+            .splitn(2, '?')
+            .nth(1)
+            .and_then(|query| query.split('&').find(|p| p.starts_with(SESSION_PARAM)))
+            .map(|param| param[SESSION_PARAM.len()..].to_string())
+            .and_then(|token| {
+                self.sessions
+                    .resume(&token, owner)
+                    .map(|state| (token, state))
+            });
+
+        match resumed {
+            Some((token, state)) => {
+                self.token = Some(token);
+                self.state = state;
+                Some(true)
+            }
+            None => {
+                self.token = Some(self.sessions.create(self.state.clone(), owner)?);
+                Some(false)
+            }
+        }
+    }
+
+    /// Advance the game by one turn. Returns false if the game should be paused.
+    fn next_turn(&self, game: &mut Game, queue: &mut MessageQueue) -> bool {
         if game.is_over() {
             queue.push(Message::Status("Grid has stabilized."));
         }
         game.tick();
-        queue.push(Message::Grid(game.draw()));
+        queue.push_frame(game);
+        if let Err(err) = self.limits.check_population(game) {
+            queue.push(Message::Error(ErrorReply::new(ERR_LIMIT_EXCEEDED, err)));
+            return false;
+        }
+        true
+    }
+
+    /// Validate a GameConfig in full and build a new Game from it.
+    fn new_game(&self, config: GameConfig) -> Result<Game, ErrorReply> {
+        let limit_exceeded = |err: String| ErrorReply::new(ERR_LIMIT_EXCEEDED, err);
+        self.limits.check_config(&config).map_err(limit_exceeded)?;
+        let game = config.build_with(&*self.library)?;
+        self.limits.check_game(&game).map_err(limit_exceeded)?;
+        Ok(game.with_history(self.limits.history()))
+    }
+
+    /// Check a saved game against the current limits, which may be stricter than the ones it was
+    /// saved under. The initial game is checked like a new one, and the current game like a
+    /// running one.
+    fn check_loaded(&self, (game, initial_game): (Game, Game)) -> Result<(Game, Game), ErrorReply> {
+        let limit_exceeded = |err: String| ErrorReply::new(ERR_LIMIT_EXCEEDED, err);
+        self.limits
+            .check_game(&initial_game)
+            .and_then(|()| {
+                self.limits
+                    .check_size(game.viewport.width, game.viewport.height)
+            })
+            .and_then(|()| self.limits.check_population(&game))
+            .map_err(limit_exceeded)?;
+        Ok((
+            game.with_history(self.limits.history()),
+            initial_game.with_history(self.limits.history()),
+        ))
+    }
+
+    /// Generate a random soup from a `WxH:density:seed[:symmetry]` spec and build a new Game
+    /// from it, keeping the settings and viewport size of the current Game.
+    fn new_soup(&self, spec: &str, current: &Game) -> Result<Game, ErrorReply> {
+        let limit_exceeded = |err: String| ErrorReply::new(ERR_LIMIT_EXCEEDED, err);
+        let spec: SoupSpec = spec.parse()?;
+        // Soups are generated rather than uploaded, so they are limited by size instead of by
+        // the length of their source.
+        self.limits
+            .check_size(spec.width, spec.height)
+            .map_err(limit_exceeded)?;
+        let game = Game::new(
+            spec.generate(),
+            current.opts.clone(),
+            (Some(current.viewport.width), Some(current.viewport.height)),
+        );
+        self.limits.check_game(&game).map_err(limit_exceeded)?;
+        Ok(game.with_history(self.limits.history()))
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+        if let Some(ref token) = self.token {
+            self.sessions.detach(token, self.out.connection_id());
+        }
     }
 }
 
@@ -170,69 +404,194 @@ impl ws::Handler for Server {
         if let Some(addr) = shake.remote_addr()? {
             debug!("Connection with {} now open", addr);
         }
-        let &mut State { ref mut queue, .. }: &mut State = &mut *self.state.lock().unwrap();
-        queue.push(Message::Connected("Connected to game server."));
+        let resumed = if self.over_capacity {
+            None
+        } else {
+            self.attach(shake.request.resource())
+        };
+        let resumed = match resumed {
+            Some(resumed) => resumed,
+            None => {
+                self.over_capacity = true;
+                let mut queue = MessageQueue::new();
+                queue.push(Message::Error(ErrorReply::new(
+                    ERR_SERVER_FULL,
+                    "the server is at capacity; please try again later",
+                )));
+                queue.flush(&self.out)?;
+                return self.out.close(ws::CloseCode::Again);
+            }
+        };
+        let &mut State {
+            ref game,
+            ref mut queue,
+            ..
+        }: &mut State = &mut *self.state.lock().unwrap();
+        queue.push(Message::Connected(self.token.clone().unwrap()));
+        if resumed {
+            queue.push(Message::Status("Resumed your previous game."));
+            queue.push_frame(game);
+        }
         queue.flush(&self.out)
     }
 
     fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
         debug!("Received message: {:?}", msg);
+        if self.over_capacity {
+            return Ok(());
+        }
         let &mut State {
             ref mut game,
+            ref mut initial_game,
+            ref mut paused,
             ref mut queue,
         }: &mut State = &mut *self.state.lock().unwrap();
 
+        if msg.len() > self.limits.max_message_bytes {
+            queue.push(Message::Error(ErrorReply::new(
+                ERR_LIMIT_EXCEEDED,
+                format!(
+                    "message is too large: the limit is {} bytes",
+                    self.limits.max_message_bytes
+                ),
+            )));
+            return queue.flush(&self.out);
+        }
+        if !self.rate_limiter.check() {
+            queue.push(Message::Error(ErrorReply::new(
+                ERR_RATE_LIMITED,
+                "too many commands; slow down",
+            )));
+            return queue.flush(&self.out);
+        }
+
         match serde_json::from_str(msg.as_text()?) {
             Ok(Cmd::Ping) => {
-                if !self.paused {
-                    self.next_turn(game, queue);
+                if !*paused && !self.next_turn(game, queue) {
+                    *paused = true;
                 }
             }
             Ok(Cmd::Step) => {
-                if self.paused {
+                if *paused {
                     self.next_turn(game, queue);
                 } else {
-                    self.paused = true;
+                    *paused = true;
+                }
+            }
+            Ok(Cmd::StepBack(n)) => {
+                *paused = true;
+                match game.step_back(n) {
+                    Ok(()) => queue.push_frame(game),
+                    Err(err) => queue.push(Message::Error(ErrorReply::from(err))),
                 }
             }
             Ok(Cmd::Play) => {
-                if self.paused {
-                    self.paused = false;
-                    self.next_turn(game, queue);
+                if *paused {
+                    *paused = !self.next_turn(game, queue);
                 }
             }
             Ok(Cmd::Pause) => {
-                self.paused = true;
+                *paused = true;
             }
             Ok(Cmd::Toggle) => {
-                if self.paused {
-                    self.next_turn(game, queue);
+                if *paused {
+                    *paused = !self.next_turn(game, queue);
+                } else {
+                    *paused = true;
                 }
-                self.paused = !self.paused;
             }
             Ok(Cmd::Scroll(dx, dy)) => {
                 game.viewport.scroll(dx, dy);
-                queue.push(Message::Grid(game.draw()));
+                queue.push_frame(game);
             }
             Ok(Cmd::Center) => {
                 game.center_viewport();
-                queue.push(Message::Grid(game.draw()));
+                queue.push_frame(game);
             }
-            Ok(Cmd::NewGrid(config)) => {
-                match config.build() {
-                    Ok(new_game) => *game = new_game,
-                    Err(err) => queue.push(Message::Error(err.to_string_chain())),
-                };
-                self.initial_game = game.clone();
-                queue.push(Message::Status("Started a new game."));
-                queue.push(Message::Grid(game.draw()));
+            Ok(Cmd::NewGrid(config)) => match self.new_game(config) {
+                Ok(new_game) => {
+                    *game = new_game;
+                    *initial_game = game.clone();
+                    queue.push(Message::Status("Started a new game."));
+                    queue.push_frame(game);
+                }
+                Err(err) => queue.push(Message::Error(err)),
+            },
+            Ok(Cmd::Soup(spec)) => match self.new_soup(&spec, game) {
+                Ok(new_game) => {
+                    *game = new_game;
+                    *initial_game = game.clone();
+                    queue.push(Message::Status("Started a new soup."));
+                    queue.push_frame(game);
+                }
+                Err(err) => queue.push(Message::Error(err)),
+            },
+            Ok(Cmd::UpdateSettings(update)) => match game.update_settings(&update) {
+                Ok(()) => {
+                    // Keep the new settings when the game is restarted.
+                    initial_game.opts = game.opts.clone();
+                    queue.push(Message::Status("Updated settings."));
+                    queue.push_frame(game);
+                }
+                Err(err) => queue.push(Message::Error(ErrorReply::from(err))),
+            },
+            Ok(Cmd::Resize(width, height)) => {
+                let resized = self
+                    .limits
+                    .check_size(width, height)
+                    .map_err(|err| ErrorReply::new(ERR_LIMIT_EXCEEDED, err))
+                    .and_then(|()| {
+                        game.resize(width, height)?;
+                        initial_game.resize(width, height)?;
+                        Ok(())
+                    });
+                match resized {
+                    Ok(()) => queue.push_frame(game),
+                    Err(err) => queue.push(Message::Error(err)),
+                }
             }
             Ok(Cmd::Restart) => {
-                *game = self.initial_game.clone();
+                *game = initial_game.clone();
                 queue.push(Message::Status("Restarted the current game."));
-                queue.push(Message::Grid(game.draw()));
+                queue.push_frame(game);
             }
-            Err(err) => queue.push(Message::Error(format!("invalid input: {}", err))),
+            Ok(Cmd::Save) => match self.store.save(&SavedGame::new(game, initial_game)) {
+                Ok(id) => queue.push(Message::Saved(id)),
+                Err(err) => {
+                    error!("Failed to save game: {}", err);
+                    queue.push(Message::Error(ErrorReply::new(
+                        ERR_INTERNAL,
+                        "failed to save game",
+                    )));
+                }
+            },
+            Ok(Cmd::Load(id)) => match self.store.load(&id) {
+                Ok(Some(saved)) => match self.check_loaded(saved.into_games()) {
+                    Ok((loaded, loaded_initial)) => {
+                        *game = loaded;
+                        *initial_game = loaded_initial;
+                        *paused = true;
+                        queue.push(Message::Status(format!("Loaded game {}.", id)));
+                        queue.push_frame(game);
+                    }
+                    Err(err) => queue.push(Message::Error(err)),
+                },
+                Ok(None) => queue.push(Message::Error(ErrorReply::new(
+                    ERR_NOT_FOUND,
+                    format!("no saved game with ID '{}'", id),
+                ))),
+                Err(err) => {
+                    error!("Failed to load game {}: {}", id, err);
+                    queue.push(Message::Error(ErrorReply::new(
+                        ERR_INTERNAL,
+                        format!("failed to load game '{}'", id),
+                    )));
+                }
+            },
+            Err(err) => queue.push(Message::Error(ErrorReply::new(
+                ERR_INVALID_COMMAND,
+                format!("invalid input: {}", err),
+            ))),
         };
 
         queue.flush(&self.out)
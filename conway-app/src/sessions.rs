@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use store::new_id;
+
+struct Entry<T> {
+    state: Arc<Mutex<T>>,
+    /// The ID of the connection currently attached to the session.
+    owner: u32,
+    /// When the owner disconnected, or `None` if it is still connected.
+    detached_at: Option<Instant>,
+}
+
+/// A SessionStore keeps session state alive across reconnects.
+///
+/// Each session is identified by a token. When a connection closes, its session is kept around
+/// for a grace period, during which a new connection can pick it up again with the token. At most
+/// `capacity` sessions are kept, counting both attached and detached ones.
+pub struct SessionStore<T> {
+    grace: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, Entry<T>>>,
+}
+
+impl<T> SessionStore<T> {
+    pub fn new(grace: Duration, capacity: usize) -> Self {
+        SessionStore {
+            grace,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register a new session owned by the given connection, and return its token.
+    ///
+    /// If the store is full, the session that was detached the longest ago is dropped to make
+    /// room. Returns `None` if the store is full of attached sessions.
+    pub fn create(&self, state: Arc<Mutex<T>>, owner: u32) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        self.expire(&mut entries);
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .filter_map(|(token, entry)| entry.detached_at.map(|at| (at, token)))
+                .min()
+                .map(|(_, token)| token.clone())?;
+            entries.remove(&oldest);
+        }
+        let token = new_id();
+        entries.insert(
+            token.clone(),
+            Entry {
+                state,
+                owner,
+                detached_at: None,
+            },
+        );
+        Some(token)
+    }
+
+    /// Hand the session with the given token over to a new connection.
+    ///
+    /// Returns `None` if there is no such session, if it has expired, or if it's still attached
+    /// to another connection.
+    pub fn resume(&self, token: &str, owner: u32) -> Option<Arc<Mutex<T>>> {
+        let mut entries = self.entries.lock().unwrap();
+        self.expire(&mut entries);
+        match entries.get_mut(token) {
+            Some(entry) if entry.detached_at.is_some() || entry.owner == owner => {
+                entry.owner = owner;
+                entry.detached_at = None;
+                Some(entry.state.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Start the grace period for a session whose connection has closed.
+    ///
+    /// Does nothing if another connection has since resumed the session.
+    pub fn detach(&self, token: &str, owner: u32) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get_mut(token) {
+            if entry.owner == owner {
+                entry.detached_at = Some(Instant::now());
+            }
+        }
+        self.expire(&mut entries);
+    }
+
+    fn expire(&self, entries: &mut HashMap<String, Entry<T>>) {
+        let grace = self.grace;
+        entries.retain(|_, entry| {
+            entry
+                .detached_at
+                .map_or(true, |detached_at| detached_at.elapsed() < grace)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    fn mk_state(n: u32) -> Arc<Mutex<u32>> {
+        Arc::new(Mutex::new(n))
+    }
+
+    #[test]
+    fn test_resume() {
+        let store = SessionStore::new(Duration::from_secs(60), 4);
+        let token = store.create(mk_state(7), 1).unwrap();
+        assert!(store.resume("nope", 1).is_none());
+
+        // Still attached to its first connection, so only that one may pick it up.
+        assert!(store.resume(&token, 2).is_none());
+        assert_eq!(*store.resume(&token, 1).unwrap().lock().unwrap(), 7);
+
+        store.detach(&token, 1);
+        assert_eq!(*store.resume(&token, 2).unwrap().lock().unwrap(), 7);
+        assert!(store.resume(&token, 1).is_none());
+
+        // The old connection closing late must not detach the new one.
+        store.detach(&token, 1);
+        assert!(store.resume(&token, 3).is_none());
+        store.detach(&token, 2);
+        assert!(store.resume(&token, 3).is_some());
+    }
+
+    #[test]
+    fn test_expire() {
+        let store = SessionStore::new(Duration::from_secs(0), 4);
+        let token = store.create(mk_state(0), 1).unwrap();
+        store.detach(&token, 1);
+        assert!(store.resume(&token, 1).is_none());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let store = SessionStore::new(Duration::from_secs(60), 3);
+        let first = store.create(mk_state(1), 1).unwrap();
+        let second = store.create(mk_state(2), 2).unwrap();
+        let third = store.create(mk_state(3), 3).unwrap();
+        assert!(store.create(mk_state(4), 4).is_none());
+
+        store.detach(&second, 2);
+        thread::sleep(Duration::from_millis(10));
+        store.detach(&first, 1);
+        let fourth = store.create(mk_state(4), 4).unwrap();
+        assert!(store.resume(&second, 5).is_none());
+        assert!(store.resume(&first, 5).is_some());
+        assert!(store.resume(&third, 3).is_some());
+        assert!(store.resume(&fourth, 4).is_some());
+    }
+}
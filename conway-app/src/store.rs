@@ -0,0 +1,150 @@
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use conway::config::Settings;
+use conway::game::Viewport;
+use conway::{Game, Grid};
+
+/// Everything needed to bring a game back exactly as it was when it was saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub grid: Grid,
+    pub generation: u64,
+    pub settings: Settings,
+    pub viewport: Viewport,
+    /// The game as it was at generation 0, so that `Restart` still works after loading.
+    pub initial: Grid,
+    pub initial_viewport: Viewport,
+}
+
+impl SavedGame {
+    pub fn new(game: &Game, initial_game: &Game) -> Self {
+        SavedGame {
+            grid: game.grid().clone(),
+            generation: game.generation(),
+            settings: game.opts.clone(),
+            viewport: game.viewport.clone(),
+            initial: initial_game.grid().clone(),
+            initial_viewport: initial_game.viewport.clone(),
+        }
+    }
+
+    /// Rebuild the saved game, returning the current and initial games.
+    pub fn into_games(self) -> (Game, Game) {
+        let game = Game::restore(
+            self.grid,
+            self.generation,
+            self.settings.clone(),
+            self.viewport,
+        );
+        let initial_game = Game::restore(self.initial, 0, self.settings, self.initial_viewport);
+        (game, initial_game)
+    }
+}
+
+/// A GameStore keeps saved games as JSON files in a directory, one file per game.
+pub struct GameStore {
+    dir: PathBuf,
+}
+
+impl GameStore {
+    /// Open a GameStore in `dir`, creating the directory if it doesn't exist.
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(GameStore { dir })
+    }
+
+    /// Save a game and return the ID it can be loaded with.
+    pub fn save(&self, game: &SavedGame) -> io::Result<String> {
+        let id = new_id();
+        let json = serde_json::to_string(game).map_err(io::Error::from)?;
+        fs::write(self.path(&id), json)?;
+        Ok(id)
+    }
+
+    /// Load the game with the given ID, or return `None` if there isn't one.
+    pub fn load(&self, id: &str) -> io::Result<Option<SavedGame>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+        match fs::read_to_string(self.path(id)) {
+            Ok(json) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(io::Error::from),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+/// Generate a new, hard to guess, hexadecimal ID.
+pub fn new_id() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    // `RandomState` is seeded randomly, which is all the randomness needed here.
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(COUNTER.fetch_add(1, Ordering::SeqCst));
+    if let Ok(elapsed) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+// IDs are used as file names, so anything that isn't one of our own IDs is rejected outright.
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 16 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = env::temp_dir().join(format!("conway-store-{}", process::id()));
+        let store = GameStore::open(dir.clone()).unwrap();
+        let glider: Grid = ".x.\n..x\nxxx".parse().unwrap();
+        let initial = Game::new(glider, Settings::default(), (Some(8), Some(8)));
+        let mut game = initial.clone();
+        game.tick();
+
+        let id = store.save(&SavedGame::new(&game, &initial)).unwrap();
+        let (loaded, loaded_initial) = store.load(&id).unwrap().unwrap().into_games();
+        assert_eq!(loaded.grid(), game.grid());
+        assert_eq!(loaded.generation(), 1);
+        assert_eq!(loaded.viewport, game.viewport);
+        assert_eq!(loaded.opts, game.opts);
+        assert_eq!(loaded_initial.grid(), initial.grid());
+        assert_eq!(loaded_initial.generation(), 0);
+
+        assert!(store.load(&new_id()).unwrap().is_none());
+        for id in &["", "../secret", "0123456789abcdeg", "0123456789abcdef0"] {
+            assert!(store.load(id).unwrap().is_none(), "{}", id);
+        }
+        let corrupt = new_id();
+        fs::write(store.path(&corrupt), "{").unwrap();
+        assert!(store.load(&corrupt).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_new_id() {
+        let (a, b) = (new_id(), new_id());
+        assert_ne!(a, b);
+        assert!(is_valid_id(&a) && is_valid_id(&b));
+    }
+}
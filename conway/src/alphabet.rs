@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+
+use grid::{COMMENT_CHAR, READ_CHAR_ALIVE, READ_CHAR_DEAD};
+use {ErrorKind, Result};
+
+/// The most live or dead characters an Alphabet can have.
+pub const MAX_ALPHABET_LEN: usize = 32;
+
+/// An Alphabet is the set of characters that a pattern is written with.
+///
+/// Any of the `alive` characters is read as a live cell, and any of the `dead` characters as a
+/// dead cell. The first of each is used when writing a pattern. The rest of a line after the
+/// `comment` character is a comment; with no comment character, every line is a row of cells.
+///
+/// The default Alphabet is the standard pattern format: `x` for live cells, `.` for dead cells
+/// and `#` for comments.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Alphabet {
+    pub alive: String,
+    pub dead: String,
+    pub comment: Option<char>,
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Alphabet {
+            alive: READ_CHAR_ALIVE.to_string(),
+            dead: READ_CHAR_DEAD.to_string(),
+            comment: Some(COMMENT_CHAR),
+        }
+    }
+}
+
+impl Alphabet {
+    /// Create an Alphabet with the given live and dead characters. Comments start with `#`,
+    /// unless it's one of the cell characters, in which case there are no comments.
+    pub fn new(alive: &str, dead: &str) -> Self {
+        let comment = if alive.contains(COMMENT_CHAR) || dead.contains(COMMENT_CHAR) {
+            None
+        } else {
+            Some(COMMENT_CHAR)
+        };
+        Alphabet {
+            alive: alive.to_string(),
+            dead: dead.to_string(),
+            comment,
+        }
+    }
+
+    /// Check that the Alphabet has at least one and at most MAX_ALPHABET_LEN live and dead
+    /// characters, that no character has two meanings, and that every character is visible.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| ErrorKind::InvalidConfig("alphabet", reason.to_string());
+        if self.alive.is_empty() || self.dead.is_empty() {
+            bail!(invalid("needs at least one live and one dead character"));
+        }
+        if self.alive.chars().count() > MAX_ALPHABET_LEN
+            || self.dead.chars().count() > MAX_ALPHABET_LEN
+        {
+            bail!(invalid(&format!(
+                "can have at most {} live and {} dead characters",
+                MAX_ALPHABET_LEN, MAX_ALPHABET_LEN
+            )));
+        }
+        let chars: Vec<char> = self
+            .alive
+            .chars()
+            .chain(self.dead.chars())
+            .chain(self.comment)
+            .collect();
+        if chars.iter().any(|ch| ch.is_control() || ch.is_whitespace()) {
+            bail!(invalid("characters must be visible"));
+        }
+        let mut seen = HashSet::new();
+        for ch in chars {
+            if !seen.insert(ch) {
+                bail!(invalid(&format!("'{}' is used more than once", ch)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return whether `ch` is one of the live characters.
+    pub fn is_alive(&self, ch: char) -> bool {
+        self.alive.contains(ch)
+    }
+
+    /// Return whether `ch` is one of the dead characters.
+    pub fn is_dead(&self, ch: char) -> bool {
+        self.dead.contains(ch)
+    }
+
+    /// Return whether `ch` starts a comment.
+    pub fn is_comment(&self, ch: char) -> bool {
+        self.comment == Some(ch)
+    }
+
+    /// Return the character to write live cells with.
+    pub fn alive_char(&self) -> char {
+        self.alive.chars().next().unwrap_or(READ_CHAR_ALIVE)
+    }
+
+    /// Return the character to write dead cells with.
+    pub fn dead_char(&self) -> char {
+        self.dead.chars().next().unwrap_or(READ_CHAR_DEAD)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let alphabet = Alphabet::new("x■", ".□");
+        assert_eq!(alphabet.comment, Some('#'));
+        assert!(alphabet.is_alive('■') && alphabet.is_dead('.') && !alphabet.is_dead('x'));
+        assert_eq!((alphabet.alive_char(), alphabet.dead_char()), ('x', '.'));
+        assert!(alphabet.validate().is_ok());
+
+        let alphabet = Alphabet::new("#", "-");
+        assert_eq!(alphabet.comment, None);
+        assert!(alphabet.is_alive('#') && !alphabet.is_comment('#'));
+        assert!(alphabet.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Alphabet::default().validate().is_ok());
+        let letters = |n: usize| (b'A'..).take(n).map(char::from).collect::<String>();
+        assert!(Alphabet::new(&letters(MAX_ALPHABET_LEN), ".")
+            .validate()
+            .is_ok());
+        for alphabet in &[
+            Alphabet::new("", "."),
+            Alphabet::new("x", ""),
+            Alphabet::new("xo", ".o"),
+            Alphabet::new("x ", "."),
+            Alphabet::new(&letters(MAX_ALPHABET_LEN + 1), "."),
+            Alphabet {
+                comment: Some('x'),
+                ..Alphabet::default()
+            },
+        ] {
+            assert!(alphabet.validate().is_err(), "{:?}", alphabet);
+        }
+    }
+}
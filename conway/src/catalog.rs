@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use layer::config_dir;
+use pattern::Pattern;
+use Result;
+
+// The built-in patterns, embedded from the `sample_patterns` directory.
+static BUILTIN_PATTERNS: &[(&str, &str)] = &[
+    ("beacon", include_str!("../sample_patterns/beacon")),
+    ("blinker", include_str!("../sample_patterns/blinker")),
+    ("glider", include_str!("../sample_patterns/glider")),
+    ("toad", include_str!("../sample_patterns/toad")),
+];
+
+lazy_static! {
+    /// The sample patterns: the built-in ones, plus any in the user's pattern directory.
+    pub static ref SAMPLES: PatternCatalog = PatternCatalog::load(|name| env::var(name).ok());
+}
+
+/// Metadata from the comment header at the top of a pattern file.
+///
+/// A header looks like this:
+///
+/// ```text
+/// # Glider
+/// # Type: Spaceship
+/// # Period: 4
+/// # Author: Richard K. Guy
+/// ```
+///
+/// The title is the Pattern's name; see `Pattern` for how the header is read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatternMeta {
+    pub title: Option<String>,
+    pub kind: Option<String>,
+    pub period: Option<u64>,
+    pub author: Option<String>,
+}
+
+impl From<&Pattern> for PatternMeta {
+    fn from(pattern: &Pattern) -> Self {
+        PatternMeta {
+            title: pattern.name.clone(),
+            kind: pattern.field("type").map(str::to_string),
+            period: pattern.field("period").and_then(|value| value.parse().ok()),
+            author: pattern.author.clone(),
+        }
+    }
+}
+
+/// A pattern in a PatternCatalog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogEntry {
+    pub name: String,
+    pub meta: PatternMeta,
+    pub source: String,
+    /// The file the pattern was loaded from, or `None` for built-in patterns.
+    pub path: Option<PathBuf>,
+}
+
+/// A PatternCatalog is a set of named patterns, loaded from the built-in samples and from
+/// pattern directories. Each pattern is named after its file, without the extension.
+#[derive(Debug, Clone, Default)]
+pub struct PatternCatalog {
+    entries: BTreeMap<String, CatalogEntry>,
+}
+
+impl PatternCatalog {
+    /// Create a PatternCatalog of the built-in patterns.
+    pub fn builtin() -> Self {
+        let mut catalog = PatternCatalog::default();
+        for &(name, source) in BUILTIN_PATTERNS {
+            catalog
+                .insert(name, source.to_string(), None)
+                .expect("invalid built-in pattern");
+        }
+        catalog
+    }
+
+    /// Create a PatternCatalog of the built-in patterns and those in the user's pattern
+    /// directory, `conway/patterns` in the config directory. Environment variables are looked up
+    /// with `var`.
+    ///
+    /// User patterns replace built-in patterns with the same name. A missing or unreadable user
+    /// directory is ignored.
+    pub fn load<F: Fn(&str) -> Option<String>>(var: F) -> Self {
+        let mut catalog = PatternCatalog::builtin();
+        if let Some(dir) = config_dir(var).map(|dir| dir.join("patterns")) {
+            let _ = catalog.add_dir(&dir);
+        }
+        catalog
+    }
+
+    /// Add every pattern file in `dir` to the catalog. Files that can't be read as text or fail to
+    /// parse are skipped.
+    pub fn add_dir(&mut self, dir: &Path) -> io::Result<()> {
+        for file in fs::read_dir(dir)? {
+            let path = file?.path();
+            let name = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(name) if path.is_file() && !name.starts_with('.') => name.to_string(),
+                _ => continue,
+            };
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(_) => continue,
+            };
+            let _ = self.insert(&name, source, Some(path));
+        }
+        Ok(())
+    }
+
+    /// Validate a pattern and add it to the catalog, replacing any pattern with the same name.
+    pub fn insert(&mut self, name: &str, source: String, path: Option<PathBuf>) -> Result<()> {
+        let pattern: Pattern = source.parse()?;
+        self.entries.insert(
+            name.to_string(),
+            CatalogEntry {
+                name: name.to_string(),
+                meta: PatternMeta::from(&pattern),
+                source,
+                path,
+            },
+        );
+        Ok(())
+    }
+
+    /// Return the pattern with the given name.
+    pub fn get(&self, name: &str) -> Option<&CatalogEntry> {
+        self.entries.get(name)
+    }
+
+    /// Return an iterator over the patterns in the catalog, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &CatalogEntry> {
+        self.entries.values()
+    }
+}
+
+/// Write the catalog as a table, one pattern per line.
+impl fmt::Display for PatternCatalog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .entries
+            .keys()
+            .map(|name| name.len())
+            .max()
+            .unwrap_or(0);
+        for entry in self.iter() {
+            let meta = &entry.meta;
+            write!(
+                f,
+                "{:<width$}  {:<12} {:>4}",
+                entry.name,
+                meta.kind.as_ref().map_or("-", String::as_str),
+                meta.period.map_or("-".to_string(), |p| format!("p{}", p)),
+                width = width
+            )?;
+            if let Some(ref title) = meta.title {
+                write!(f, "  {}", title)?;
+            }
+            if let Some(ref author) = meta.author {
+                write!(f, " ({})", author)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use census::classify;
+
+    #[test]
+    fn test_parse_meta() {
+        let pattern = "# Toad\n#type: Oscillator\n# Period: 2\n# Author: X\nxx\n# Y"
+            .parse::<Pattern>()
+            .unwrap();
+        assert_eq!(
+            PatternMeta::from(&pattern),
+            PatternMeta {
+                title: Some("Toad".to_string()),
+                kind: Some("Oscillator".to_string()),
+                period: Some(2),
+                author: Some("X".to_string()),
+            }
+        );
+        assert_eq!(
+            PatternMeta::from(&"xx".parse::<Pattern>().unwrap()),
+            PatternMeta::default()
+        );
+    }
+
+    // Each sample's declared type and period should match what it does when it's run.
+    #[test]
+    fn test_builtin_types_and_periods() {
+        for entry in PatternCatalog::builtin().iter() {
+            let code = classify(&entry.source.parse().unwrap());
+            let period = code[2..code.find('_').unwrap()].parse().ok();
+            let (kind, period) = match &code[..2] {
+                "xs" => ("still life", Some(1)),
+                "xp" => ("oscillator", period),
+                "xq" => ("spaceship", period),
+                _ => panic!("{} is unstable", entry.name),
+            };
+            let meta = &entry.meta;
+            assert_eq!(
+                meta.kind.as_ref().map(|kind| kind.to_lowercase()),
+                Some(kind.to_string()),
+                "{}",
+                entry.name
+            );
+            assert_eq!(meta.period, period, "{}", entry.name);
+            assert!(
+                meta.title.is_some() && meta.author.is_some(),
+                "{}",
+                entry.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_beacon() {
+        let catalog = PatternCatalog::builtin();
+        let beacon = &catalog.get("beacon").unwrap().source;
+        assert_ne!(beacon, &catalog.get("blinker").unwrap().source);
+        assert_eq!(classify(&beacon.parse().unwrap()), "xp2_318c");
+    }
+
+    #[test]
+    fn test_add_dir() {
+        let dir = env::temp_dir().join(format!("conway-catalog-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("glider.txt"), "# My glider\n.x.\n..x\nxxx").unwrap();
+        fs::write(
+            dir.join("pond"),
+            "# Type: Still life\n.xx.\nx..x\nx..x\n.xx.",
+        )
+        .unwrap();
+        fs::write(dir.join("broken"), "xo?").unwrap();
+        fs::write(dir.join(".hidden"), "xx").unwrap();
+        fs::write(dir.join("binary"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let mut catalog = PatternCatalog::builtin();
+        catalog.add_dir(&dir).unwrap();
+        let glider = catalog.get("glider").unwrap();
+        assert_eq!(glider.meta.title, Some("My glider".to_string()));
+        assert_eq!(glider.path, Some(dir.join("glider.txt")));
+        assert_eq!(
+            catalog.get("pond").unwrap().meta.kind,
+            Some("Still life".to_string())
+        );
+        assert!(catalog.get("broken").is_none() && catalog.get("binary").is_none());
+        assert!(catalog.get(".hidden").is_none() && catalog.get("").is_none());
+        assert!(catalog.get("toad").is_some());
+        assert!(catalog.add_dir(&dir.join("missing")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
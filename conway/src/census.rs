@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use config::Settings;
+use game::Game;
+use grid::Grid;
+use history::History;
+use soup::{SoupSpec, Symmetry};
+use Result;
+
+/// The longest period of oscillators and spaceships that objects are checked for.
+pub const MAX_PERIOD: u64 = 30;
+/// How many times the population has to repeat its cycle before a soup counts as stable.
+const STABLE_CYCLES: usize = 4;
+/// Cells within this distance of each other are counted as part of the same object.
+const OBJECT_DISTANCE: u64 = 2;
+/// The code given to objects that don't settle into a known period.
+pub const UNSTABLE_CODE: &str = "zz_UNSTABLE";
+
+/// Configuration for a census of random soups.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CensusConfig {
+    /// The number of soups to run.
+    pub soups: u64,
+    /// The seed of the first soup. Soups use consecutive seeds starting from here.
+    pub first_seed: u64,
+    pub width: u64,
+    pub height: u64,
+    pub density: f64,
+    pub symmetry: Symmetry,
+    /// The number of threads to run soups on.
+    pub threads: usize,
+    /// The maximum number of generations to run each soup for before giving up on it settling.
+    pub max_generations: u64,
+}
+
+impl Default for CensusConfig {
+    fn default() -> Self {
+        CensusConfig {
+            soups: 1000,
+            first_seed: 0,
+            width: 16,
+            height: 16,
+            density: 0.5,
+            symmetry: Symmetry::C1,
+            threads: 4,
+            max_generations: 5000,
+        }
+    }
+}
+
+impl CensusConfig {
+    /// Return the SoupSpec for the soup with the given seed.
+    pub fn soup(&self, seed: u64) -> SoupSpec {
+        SoupSpec::new(self.width, self.height, self.density, seed).with_symmetry(self.symmetry)
+    }
+
+    /// Run the census, spreading the soups across `threads` threads.
+    pub fn run(&self) -> Result<CensusReport> {
+        self.soup(self.first_seed).validate()?;
+        let threads = self.threads.max(1) as u64;
+        let handles: Vec<_> = (0..threads)
+            .map(|offset| {
+                let config = self.clone();
+                thread::spawn(move || {
+                    let mut report = CensusReport::default();
+                    let mut i = offset;
+                    while i < config.soups {
+                        let soup = config.soup(config.first_seed + i);
+                        report.add(&soup, &run_soup(&soup, config.max_generations));
+                        i += threads;
+                    }
+                    report
+                })
+            })
+            .collect();
+
+        let mut report = CensusReport::default();
+        for handle in handles {
+            report.merge(handle.join().expect("census thread panicked"));
+        }
+        Ok(report)
+    }
+}
+
+/// The results of a census: how many of each object were found, and a soup for each object.
+#[derive(Debug, Clone, Default)]
+pub struct CensusReport {
+    /// The number of soups that were run.
+    pub soups: u64,
+    /// The number of times each object was found, by object code.
+    pub counts: BTreeMap<String, u64>,
+    /// The soup with the lowest seed that produced each object, by object code.
+    pub samples: BTreeMap<String, SoupSpec>,
+}
+
+impl CensusReport {
+    /// Record the objects that a soup settled into.
+    fn add(&mut self, soup: &SoupSpec, objects: &[String]) {
+        self.soups += 1;
+        for code in objects {
+            *self.counts.entry(code.clone()).or_insert(0) += 1;
+            self.keep_sample(code, soup);
+        }
+    }
+
+    /// Combine the results of another census into this one.
+    fn merge(&mut self, other: CensusReport) {
+        self.soups += other.soups;
+        for (code, count) in other.counts {
+            *self.counts.entry(code).or_insert(0) += count;
+        }
+        for (code, soup) in &other.samples {
+            self.keep_sample(code, soup);
+        }
+    }
+
+    // Keep the soup as the sample for an object, unless there is one with a lower seed already.
+    fn keep_sample(&mut self, code: &str, soup: &SoupSpec) {
+        match self.samples.get(code) {
+            Some(sample) if sample.seed <= soup.seed => (),
+            _ => {
+                self.samples.insert(code.to_string(), soup.clone());
+            }
+        }
+    }
+
+    /// Return the object codes and their counts, most common first.
+    pub fn table(&self) -> Vec<(&str, u64)> {
+        let mut table: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(code, &count)| (code.as_str(), count))
+            .collect();
+        table.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        table
+    }
+
+    /// Return the objects that were found at most `threshold` times, with a soup for each.
+    pub fn rare(&self, threshold: u64) -> Vec<(&str, &SoupSpec)> {
+        self.table()
+            .into_iter()
+            .filter(|&(_, count)| count <= threshold)
+            .map(|(code, _)| (code, &self.samples[code]))
+            .collect()
+    }
+
+    /// Save a soup for each rare object to `dir`, as pattern files that can be loaded with
+    /// `--file`. Returns the paths that were written.
+    pub fn save_rare<P: AsRef<Path>>(&self, dir: P, threshold: u64) -> Result<Vec<PathBuf>> {
+        fs::create_dir_all(&dir)?;
+        let mut paths = Vec::new();
+        for (code, soup) in self.rare(threshold) {
+            let path = dir.as_ref().join(format!("{}_{}", code, soup.seed));
+            fs::write(
+                &path,
+                format!("# Soup: {}\n# Object: {}\n{}", soup, code, soup.generate()),
+            )?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+impl fmt::Display for CensusReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total: u64 = self.counts.values().sum();
+        writeln!(f, "{} objects in {} soups", total, self.soups)?;
+        for (code, count) in self.table() {
+            writeln!(f, "{:>10}  {}", count, code)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run a soup until it stabilizes, and return the codes of the objects it settles into.
+pub fn run_soup(soup: &SoupSpec, max_generations: u64) -> Vec<String> {
+    let mut game = Game::new(soup.generate(), Settings::default(), (Some(1), Some(1)))
+        .with_history(History::disabled());
+    let mut populations = VecDeque::new();
+    let window = STABLE_CYCLES * MAX_PERIOD as usize;
+    while game.generation() < max_generations {
+        game.tick();
+        populations.push_back(game.grid().len());
+        if populations.len() > window {
+            populations.pop_front();
+        }
+        if is_stable(&populations) {
+            break;
+        }
+    }
+    // Objects that are close enough to interact are counted as one.
+    let mut codes: Vec<String> = game
+        .grid()
+        .clusters(OBJECT_DISTANCE)
+        .iter()
+        .map(|cluster| classify(&cluster.cells))
+        .collect();
+    codes.sort();
+    codes
+}
+
+// Return whether the population has been cycling with a period of at most `MAX_PERIOD` for at
+// least `STABLE_CYCLES` cycles.
+fn is_stable(populations: &VecDeque<usize>) -> bool {
+    let n = populations.len();
+    (1..=MAX_PERIOD as usize).any(|period| {
+        let span = period * STABLE_CYCLES;
+        span + period <= n
+            && (0..span).all(|i| populations[n - 1 - i] == populations[n - 1 - i - period])
+    })
+}
+
+/// Return an apgcode-style code for an object: its kind and period, followed by its smallest
+/// encoding across all phases and orientations. For example, a block is `xs4_33`.
+///
+/// Still lifes are `xs` followed by their population, oscillators `xp` and spaceships `xq`
+/// followed by their period. Objects with no period up to `MAX_PERIOD` are `UNSTABLE_CODE`.
+pub fn classify(grid: &Grid) -> String {
+    let start = grid.at_origin();
+    let mut game = Game::new(grid.clone(), Settings::default(), (Some(1), Some(1)))
+        .with_history(History::disabled());
+    let (origin, _) = game.grid().bounds();
+    let mut phases = vec![start.clone()];
+    for period in 1..=MAX_PERIOD {
+        game.tick();
+        if game.grid().is_empty() {
+            break;
+        }
+        let phase = game.grid().at_origin();
+        if phase == start {
+            let prefix = if game.grid().bounds().0 != origin {
+                format!("xq{}", period)
+            } else if period == 1 {
+                format!("xs{}", phase.len())
+            } else {
+                format!("xp{}", period)
+            };
+            let code = phases
+                .iter()
+                .map(Grid::wechsler)
+                .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+                .unwrap();
+            return format!("{}_{}", prefix, code);
+        }
+        phases.push(phase);
+    }
+    UNSTABLE_CODE.to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn code(pattern: &str) -> String {
+        classify(&pattern.parse().unwrap())
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(code("xx\nxx"), "xs4_33");
+        assert_eq!(code("xxx"), "xp2_7");
+        assert_eq!(code(".x.\n..x\nxxx"), "xq4_153");
+        assert_eq!(code(".xx.\nx..x\n.xx."), "xs6_696");
+        assert_eq!(code("x"), UNSTABLE_CODE);
+    }
+
+    // The same census should always give the same results, however many threads it runs on.
+    #[test]
+    fn test_census_reproducible() {
+        let config = CensusConfig {
+            soups: 4,
+            width: 8,
+            height: 8,
+            threads: 1,
+            ..Default::default()
+        };
+        let report = config.run().unwrap();
+        assert_eq!(report.soups, 4);
+        let threaded = CensusConfig {
+            threads: 3,
+            ..config
+        }
+        .run()
+        .unwrap();
+        assert_eq!(threaded.counts, report.counts);
+        assert_eq!(threaded.samples, report.samples);
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use game::Game;
+use Result;
+
+/// A Checkpoint saves snapshots of a running Game to a file, so that a long run can be resumed
+/// with `Game::load` if it's interrupted.
+///
+/// Each snapshot replaces the last one. A snapshot is due every `every` generations (counting
+/// from generation 0, so resuming doesn't shift them) and whenever `interval` has passed since the
+/// last one.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub path: PathBuf,
+    pub every: Option<u64>,
+    pub interval: Option<Duration>,
+    last_saved: Instant,
+}
+
+impl Checkpoint {
+    /// Create a Checkpoint that saves to the given file. Until `every` or `interval` is set, a
+    /// snapshot is only ever due when the caller decides so, e.g. when the run is interrupted.
+    pub fn new(path: PathBuf) -> Self {
+        Checkpoint {
+            path,
+            every: None,
+            interval: None,
+            last_saved: Instant::now(),
+        }
+    }
+
+    /// Return whether a snapshot of the Game is due.
+    pub fn is_due(&self, game: &Game) -> bool {
+        let by_generation = match self.every {
+            Some(every) if every > 0 => game.generation().is_multiple_of(every),
+            _ => false,
+        };
+        let by_time = match self.interval {
+            Some(interval) => self.last_saved.elapsed() >= interval,
+            None => false,
+        };
+        by_generation || by_time
+    }
+
+    /// Save a snapshot of the Game now, and start timing the interval to the next one.
+    pub fn save(&mut self, game: &Game) -> Result<()> {
+        game.save(&self.path)?;
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+
+    /// Save a snapshot of the Game if one is due. Return whether one was saved.
+    pub fn update(&mut self, game: &Game) -> Result<bool> {
+        if self.is_due(game) {
+            self.save(game)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config::Settings;
+    use grid::Grid;
+    use std::env;
+    use std::fs;
+
+    fn mk_game() -> Game {
+        let grid: Grid = ".x.\n..x\nxxx\n\n.....xx\n.....xx".parse().unwrap();
+        Game::new(grid, Settings::default(), (Some(10), Some(10)))
+    }
+
+    #[test]
+    fn test_every() {
+        let path = env::temp_dir().join(format!("conway-checkpoint-{}", ::std::process::id()));
+        let mut checkpoint = Checkpoint {
+            every: Some(4),
+            ..Checkpoint::new(path.clone())
+        };
+        let mut game = mk_game();
+        let mut saved = Vec::new();
+        for _ in 0..10 {
+            game.tick();
+            if checkpoint.update(&game).unwrap() {
+                saved.push(game.generation());
+            }
+        }
+        assert_eq!(saved, vec![4, 8]);
+        assert_eq!(Game::load(&path).unwrap().generation(), 8);
+
+        // Resuming keeps to the same schedule.
+        let mut game = Game::load(&path).unwrap();
+        game.tick();
+        checkpoint.save(&game).unwrap();
+        game.tick();
+        game.tick();
+        assert!(!checkpoint.is_due(&game));
+        game.tick();
+        assert!(checkpoint.is_due(&game));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_interval() {
+        let mut checkpoint = Checkpoint::new(PathBuf::from("unused"));
+        assert!(!checkpoint.is_due(&mk_game()));
+        checkpoint.interval = Some(Duration::from_secs(0));
+        assert!(checkpoint.is_due(&mk_game()));
+    }
+
+    // A run that's interrupted and resumed from its checkpoint should end up exactly where an
+    // uninterrupted run does.
+    #[test]
+    fn test_resume_matches_uninterrupted_run() {
+        let path = env::temp_dir().join(format!("conway-resume-{}", ::std::process::id()));
+        let mut uninterrupted = mk_game();
+        let mut frames = Vec::new();
+        for _ in 0..30 {
+            uninterrupted.tick();
+            frames.push(uninterrupted.draw());
+        }
+
+        let mut game = mk_game();
+        let mut checkpoint = Checkpoint {
+            every: Some(7),
+            ..Checkpoint::new(path.clone())
+        };
+        for _ in 0..17 {
+            game.tick();
+            checkpoint.update(&game).unwrap();
+        }
+        let mut game = Game::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(game.generation(), 14);
+        while game.generation() < 30 {
+            game.tick();
+            assert_eq!(game.draw(), frames[game.generation() as usize - 1]);
+        }
+        assert_eq!(game.grid(), uninterrupted.grid());
+        assert_eq!(game.viewport, uninterrupted.viewport);
+    }
+}
@@ -1,30 +1,34 @@
-use std::collections::BTreeMap;
 use std::default::Default;
 use std::env;
 use std::ffi::OsString;
-use std::fs::read_to_string;
-use std::path::Path;
+use std::path::PathBuf;
+use std::thread;
 use std::time::Duration;
 
-use clap::ArgMatches;
+use clap::{AppSettings, ArgMatches};
 use serde_json;
 
-use game::{Game, View};
+use alphabet::Alphabet;
+use census::CensusConfig;
+use checkpoint::Checkpoint;
+use game::{Game, OutputOptions, View};
+use grid::{Grid, Point, StampMode};
+use image::{AnimationOptions, ImageOptions};
+use layer::{find_config_file, ConfigLayer, CONFIG_VAR, PROFILE_VAR};
+use scene::{LocalResolver, PatternResolver, Placement};
+use soup::parse_size;
+use transform::Transform;
 use {ErrorKind, Result, ResultExt};
 
 const VIEW_CHOICES: &[&str] = &["centered", "fixed", "follow"];
+const OUTPUT_CHOICES: &[&str] = &["text", "json"];
+const SYMMETRY_CHOICES: &[&str] = &["C1", "C2", "C4", "D4", "D8"];
+const ROTATE_CHOICES: &[&str] = &["90", "180", "270"];
+const FLIP_CHOICES: &[&str] = &["h", "v", "horizontal", "vertical"];
 const DEFAULT_CHAR_ALIVE: &str = "#";
 const DEFAULT_CHAR_DEAD: &str = "-";
 
 lazy_static! {
-    static ref SAMPLE_DIR: &'static Path = Path::new("./sample_patterns");
-    pub static ref SAMPLE_PATTERNS: BTreeMap<&'static str, &'static str> = btreemap! {
-        "beacon" => include_str!("../sample_patterns/blinker"),
-        "blinker" => include_str!("../sample_patterns/blinker"),
-        "glider" => include_str!("../sample_patterns/glider"),
-        "toad" => include_str!("../sample_patterns/toad"),
-    };
-    static ref SAMPLE_CHOICES: Vec<&'static str> = SAMPLE_PATTERNS.keys().copied().collect();
     pub static ref CHAR_ALIVE: char = DEFAULT_CHAR_ALIVE.parse().unwrap();
     pub static ref CHAR_DEAD: char = DEFAULT_CHAR_DEAD.parse().unwrap();
 }
@@ -38,36 +42,161 @@ where
         (version: "0.1")
         (author: "Dustin Rohde <dustin.rohde@gmail.com>")
         (about: "A shell utility for running Conway's Game of Life simulations.")
-        (@group source +required =>
+        (setting: AppSettings::SubcommandsNegateReqs)
+        (@group source =>
             (@arg file: -F --file display_order(1)
                 +takes_value
                 "load a pattern from a file")
             (@arg sample: -S --sample display_order(1)
                 +takes_value
-                possible_values(SAMPLE_CHOICES.as_ref())
-                "load a sample pattern")
+                "load a sample pattern (see --list-samples)")
+            (@arg soup: --soup display_order(1)
+                +takes_value
+                "generate a random soup, given as WxH:density:seed[:symmetry]")
         )
+        (@arg resume: --resume display_order(1)
+            +takes_value
+            conflicts_with[source place]
+            "continue a game from a snapshot file")
+        (@arg checkpoint: --checkpoint display_order(9)
+            +takes_value
+            "save snapshots of the game to this file, to --resume from, and when interrupted")
+        (@arg checkpoint_every: --("checkpoint-every") display_order(9)
+            +takes_value
+            requires[checkpoint]
+            "save a snapshot every N generations")
+        (@arg checkpoint_secs: --("checkpoint-secs") display_order(9)
+            +takes_value
+            requires[checkpoint]
+            "save a snapshot every N seconds")
+        (@arg png: --png display_order(10)
+            +takes_value
+            conflicts_with[checkpoint]
+            "write a PNG image of the game to this file, instead of running it")
+        (@arg gif: --gif display_order(10)
+            +takes_value
+            conflicts_with[png checkpoint]
+            "write an animated GIF of the game to this file, instead of running it")
+        (@arg generation: --generation display_order(10)
+            +takes_value
+            "generation to draw in the image, or to start the GIF at [default: the first]")
+        (@arg frames: --frames display_order(10)
+            +takes_value
+            "number of frames in the GIF [default: 100]")
+        (@arg frame_step: --("frame-step") display_order(10)
+            +takes_value
+            "number of generations between frames in the GIF [default: 1]")
+        (@arg frame_delay: --("frame-delay") display_order(10)
+            +takes_value
+            "delay (ms) between frames in the GIF [default: 100]")
+        (@arg region: --region display_order(10)
+            +takes_value
+            "part of the grid to draw: viewport, pattern, or WxH@X,Y [default: pattern]")
+        (@arg cell_size: --("cell-size") display_order(10)
+            +takes_value
+            "width and height of each cell, in pixels [default: 8]")
+        (@arg gridlines: --gridlines display_order(10)
+            "draw lines between cells")
+        (@arg alive_color: --("alive-color") display_order(10)
+            +takes_value
+            "color of live cells, as #rrggbb [default: #000000]")
+        (@arg dead_color: --("dead-color") display_order(10)
+            +takes_value
+            "color of dead cells, as #rrggbb [default: #ffffff]")
+        (@arg gridline_color: --("gridline-color") display_order(10)
+            +takes_value
+            "color of lines between cells, as #rrggbb [default: #c0c0c0]")
+        (@arg place: -P --place display_order(1)
+            +takes_value +multiple number_of_values(1)
+            allow_hyphen_values(true)
+            "add a pattern to the scene, given as e.g. sample=glider,x=10,y=-4,rotate=90,flip=h")
         (@arg delay: -d --delay display_order(2)
-            default_value("500")
-            "delay (ms) between ticks")
+            +takes_value
+            "delay (ms) between ticks [default: 500]")
         (@arg view: -v --view display_order(3)
-            default_value[fixed]
+            +takes_value
             possible_values(VIEW_CHOICES)
-            "viewing mode")
+            "viewing mode [default: fixed]")
         (@arg width: -w --width display_order(4)
-            default_value[auto]
-            "viewport width")
+            +takes_value
+            "viewport width, or auto to fit the pattern [default: auto]")
         (@arg height: -h --height display_order(4)
-            default_value[auto]
-            "viewport height")
+            +takes_value
+            "viewport height, or auto to fit the pattern [default: auto]")
         (@arg live_char: -o --("live-char") display_order(5)
-            default_value(DEFAULT_CHAR_ALIVE)
-            env[CONWAY_LIVE_CHAR]
-            "character used to render live cells")
+            +takes_value
+            "character used to render live cells [default: #]")
         (@arg dead_char: -x --("dead-char") display_order(5)
-            default_value(DEFAULT_CHAR_DEAD)
-            env[CONWAY_DEAD_CHAR]
-            "character used to render dead cells")
+            +takes_value
+            "character used to render dead cells [default: -]")
+        (@arg read_alive: --("read-alive") display_order(5)
+            +takes_value
+            "characters read as live cells in a pattern file [default: x]")
+        (@arg read_dead: --("read-dead") display_order(5)
+            +takes_value
+            "characters read as dead cells in a pattern file [default: .]")
+        (@arg output: -O --output display_order(6)
+            +takes_value
+            possible_values(OUTPUT_CHOICES)
+            "output format [default: text]")
+        (@arg stats: -s --stats display_order(6)
+            "print population stats after each turn")
+        (@arg interactive: -i --interactive display_order(6)
+            conflicts_with[checkpoint png gif]
+            "step through the game by hand: Enter for the next generation, b [N] to go back")
+        (@arg transpose: --transpose display_order(7)
+            "mirror the pattern across its top-left to bottom-right diagonal")
+        (@arg flip: --flip display_order(7)
+            +takes_value
+            possible_values(FLIP_CHOICES)
+            "mirror the pattern horizontally or vertically")
+        (@arg rotate: --rotate display_order(7)
+            +takes_value
+            possible_values(ROTATE_CHOICES)
+            "rotate the pattern clockwise by the given number of degrees")
+        (@arg translate: --translate display_order(7)
+            +takes_value
+            allow_hyphen_values(true)
+            "move the pattern by the given offset, as (x, y)")
+        (@arg list_samples: --("list-samples") display_order(8)
+            "list the sample patterns and exit")
+        (@arg config: -c --config display_order(8)
+            +takes_value
+            "read options from a TOML or JSON file [default: ~/.config/conway/config.toml]")
+        (@arg profile: --profile display_order(8)
+            +takes_value
+            "apply the named profile from the config file")
+        (@subcommand census =>
+            (about: "Run random soups until they settle, and count the objects they leave behind.")
+            (@arg soups: -n --soups display_order(1)
+                default_value("1000")
+                "number of soups to run")
+            (@arg seed: --seed display_order(1)
+                default_value("0")
+                "seed of the first soup; the rest follow on from it")
+            (@arg size: --size display_order(2)
+                default_value("16x16")
+                "size of each soup, as WxH")
+            (@arg density: --density display_order(2)
+                default_value("0.5")
+                "chance of each cell in a soup starting out alive")
+            (@arg symmetry: --symmetry display_order(2)
+                default_value("C1")
+                possible_values(SYMMETRY_CHOICES)
+                "symmetry of each soup")
+            (@arg threads: -j --threads display_order(3)
+                +takes_value
+                "number of threads to run soups on [default: number of CPUs]")
+            (@arg max_gens: --("max-gens") display_order(3)
+                default_value("5000")
+                "number of generations to give each soup to settle")
+            (@arg rare: --rare display_order(4)
+                default_value("1")
+                "save soups for objects that were found at most this many times")
+            (@arg save_dir: --("save-dir") display_order(4)
+                default_value("census")
+                "directory to save soups for rare objects to")
+        )
     )
     .get_matches_from(args)
 }
@@ -92,17 +221,119 @@ impl Default for Settings {
     }
 }
 
+impl Settings {
+    /// Return the Alphabet that a Game with these Settings draws with, so that its output can be
+    /// parsed back into a Grid.
+    pub fn alphabet(&self) -> Alphabet {
+        Alphabet::new(&self.char_alive.to_string(), &self.char_dead.to_string())
+    }
+
+    /// Check that the Settings can be used to run a Game.
+    pub fn validate(&self) -> Result<()> {
+        if self.char_alive == self.char_dead {
+            bail!(ErrorKind::InvalidConfig(
+                "char_dead",
+                "must be different from char_alive".to_string()
+            ));
+        }
+        for &(field, ch) in &[
+            ("char_alive", self.char_alive),
+            ("char_dead", self.char_dead),
+        ] {
+            if ch.is_control() || ch.is_whitespace() {
+                bail!(ErrorKind::InvalidConfig(
+                    field,
+                    "must be a visible character".to_string()
+                ));
+            }
+        }
+        if self.view == View::Follow {
+            bail!(ErrorKind::InvalidConfig(
+                "view",
+                format!("'{}' is not supported yet", self.view)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A partial update to Settings. Fields that are `None` are left unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsUpdate {
+    pub delay: Option<Duration>,
+    pub view: Option<View>,
+    pub char_alive: Option<char>,
+    pub char_dead: Option<char>,
+}
+
+impl SettingsUpdate {
+    /// Return a copy of `settings` with this update applied.
+    pub fn apply(&self, settings: &Settings) -> Settings {
+        Settings {
+            delay: self.delay.unwrap_or(settings.delay),
+            view: self.view.clone().unwrap_or_else(|| settings.view.clone()),
+            char_alive: self.char_alive.unwrap_or(settings.char_alive),
+            char_dead: self.char_dead.unwrap_or(settings.char_dead),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GameConfig {
     #[serde(default)]
     pub settings: Settings,
+    #[serde(default)]
     pub pattern: String,
+    /// The characters `pattern` is written with. Patterns in the scene always use the standard
+    /// Alphabet.
+    #[serde(default)]
+    pub alphabet: Alphabet,
     pub bounds: (Option<u64>, Option<u64>),
+    /// A Transform applied to the pattern after it's parsed.
+    #[serde(default)]
+    pub transform: Transform,
+    /// More patterns to add to the Grid, on top of `pattern`.
+    #[serde(default)]
+    pub scene: Vec<Placement>,
 }
 
 impl GameConfig {
+    /// Validate the GameConfig and build a Game from it.
+    ///
+    /// Everything is checked before the Game is created, so an error means nothing was built.
     pub fn build(self) -> Result<Game> {
-        Ok(Game::new(self.pattern.parse()?, self.settings, self.bounds))
+        self.build_with(&LocalResolver)
+    }
+
+    /// Build a Game, loading the patterns in the scene with the given PatternResolver.
+    pub fn build_with<R: PatternResolver>(self, resolver: &R) -> Result<Game> {
+        self.settings.validate()?;
+        for &(field, size) in &[("width", self.bounds.0), ("height", self.bounds.1)] {
+            if size == Some(0) {
+                bail!(ErrorKind::InvalidConfig(
+                    field,
+                    "must be greater than 0".to_string()
+                ));
+            }
+        }
+        self.alphabet.validate()?;
+        let mut grid =
+            Grid::parse_with(&self.pattern, &self.alphabet)?.transformed(&self.transform);
+        for placement in &self.scene {
+            grid.stamp(&placement.load(resolver)?, Point::origin(), StampMode::Or);
+        }
+        // The viewport can be fit around the pattern, so its width and height have to fit in an
+        // i64.
+        let (Point(x0, y0), Point(x1, y1)) = grid.bounds();
+        let fits = |min: i64, max: i64| max.checked_sub(min).and_then(|d| d.checked_add(1));
+        if fits(x0, x1).is_none() || fits(y0, y1).is_none() {
+            bail!(ErrorKind::InvalidConfig(
+                "scene",
+                "patterns are too far apart".to_string()
+            ));
+        }
+        Ok(Game::new(grid, self.settings, self.bounds))
     }
 
     pub fn from_json(s: &str) -> Result<Self> {
@@ -117,56 +348,601 @@ impl GameConfig {
     where
         I: IntoIterator<Item = T>,
         T: Into<OsString> + Clone,
+    {
+        match Command::from_args(args)? {
+            Command::Run { config, .. } => Ok(config),
+            Command::Census { .. } | Command::ListSamples | Command::Resume { .. } => {
+                bail!("expected a pattern to run, not another command")
+            }
+        }
+    }
+}
+
+/// An image of a Game to write to a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageExport {
+    /// A PNG of one generation, or of the Game's current one if `generation` isn't given.
+    Png {
+        path: PathBuf,
+        generation: Option<u64>,
+        options: ImageOptions,
+    },
+    /// An animated GIF of a run of the Game.
+    Gif {
+        path: PathBuf,
+        options: AnimationOptions,
+    },
+}
+
+/// What to do, as given on the command line.
+#[derive(Debug)]
+pub enum Command {
+    /// Run a Game, printing each turn as `output` says, and save snapshots of it if `checkpoint`
+    /// is given. If `image` is given, draw the Game instead. If `interactive` is set, step through
+    /// the Game with commands read from stdin.
+    Run {
+        config: GameConfig,
+        output: OutputOptions,
+        checkpoint: Option<Checkpoint>,
+        image: Option<ImageExport>,
+        interactive: bool,
+    },
+    /// List the sample patterns.
+    ListSamples,
+    /// Continue a Game from a snapshot file, with any settings given on top of its own.
+    Resume {
+        path: PathBuf,
+        settings: SettingsUpdate,
+        output: OutputOptions,
+        checkpoint: Option<Checkpoint>,
+        image: Option<ImageExport>,
+        interactive: bool,
+    },
+    /// Run a census of random soups, and save soups for objects found at most `rare` times.
+    Census {
+        config: CensusConfig,
+        rare: u64,
+        save_dir: PathBuf,
+    },
+}
+
+impl Command {
+    pub fn from_argv() -> Result<Self> {
+        Command::from_args(env::args_os())
+    }
+
+    pub fn from_args<I, T>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Command::from_args_with_env(args, |name| env::var(name).ok())
+    }
+
+    /// Like `from_args`, but look up environment variables with `var`.
+    pub fn from_args_with_env<I, T, F>(args: I, var: F) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+        F: Fn(&str) -> Option<String>,
     {
         let matches = parse_args(args);
+        if matches.is_present("list_samples") {
+            return Ok(Command::ListSamples);
+        }
+        let matches = match matches.subcommand_matches("census") {
+            Some(matches) => matches,
+            None => {
+                let layer = read_layers(&matches, var)?;
+                let checkpoint = read_checkpoint(&matches)?;
+                let image = read_image(&matches)?;
+                let interactive = matches.is_present("interactive");
+                let output = layer.output_options();
+                return Ok(match matches.value_of("resume") {
+                    Some(path) => Command::Resume {
+                        path: PathBuf::from(path),
+                        settings: layer.settings_update(),
+                        output,
+                        checkpoint,
+                        image,
+                        interactive,
+                    },
+                    None => Command::Run {
+                        config: layer.into_game_config()?,
+                        output,
+                        checkpoint,
+                        image,
+                        interactive,
+                    },
+                });
+            }
+        };
 
-        let conf = GameConfig {
-            settings: Settings {
-                delay: Duration::from_millis(
-                    matches
-                        .value_of("delay")
-                        .unwrap()
-                        .parse()
-                        .map_err(|_| ErrorKind::ParseArg("delay", "an integer"))?,
-                ),
-
-                view: matches.value_of("view").unwrap().parse()?,
-
-                char_alive: matches
-                    .value_of("live_char")
-                    .unwrap()
+        let (width, height) = parse_size(matches.value_of("size").unwrap())
+            .ok_or(ErrorKind::ParseArg("size", "a size like 16x16"))?;
+        let config = CensusConfig {
+            soups: matches
+                .value_of("soups")
+                .unwrap()
+                .parse()
+                .map_err(|_| ErrorKind::ParseArg("soups", "an integer"))?,
+            first_seed: matches
+                .value_of("seed")
+                .unwrap()
+                .parse()
+                .map_err(|_| ErrorKind::ParseArg("seed", "an integer"))?,
+            width,
+            height,
+            density: matches
+                .value_of("density")
+                .unwrap()
+                .parse()
+                .map_err(|_| ErrorKind::ParseArg("density", "a number"))?,
+            symmetry: matches.value_of("symmetry").unwrap().parse()?,
+            threads: match matches.value_of("threads") {
+                Some(threads) => threads
                     .parse()
-                    .map_err(|_| ErrorKind::ParseArg("live_char", "a character"))?,
-                char_dead: matches
-                    .value_of("dead_char")
-                    .unwrap()
-                    .parse()
-                    .map_err(|_| ErrorKind::ParseArg("dead_char", "a character"))?,
+                    .map_err(|_| ErrorKind::ParseArg("threads", "an integer"))?,
+                None => thread::available_parallelism().map_or(1, |n| n.get()),
             },
-            pattern: {
-                if let Some(file) = matches.value_of("file") {
-                    read_to_string(file)?
-                } else {
-                    SAMPLE_PATTERNS
-                        .get(matches.value_of("sample").unwrap())
-                        .expect("unknown sample pattern")
-                        .to_string()
+            max_generations: matches
+                .value_of("max_gens")
+                .unwrap()
+                .parse()
+                .map_err(|_| ErrorKind::ParseArg("max_gens", "an integer"))?,
+        };
+
+        Ok(Command::Census {
+            config,
+            rare: matches
+                .value_of("rare")
+                .unwrap()
+                .parse()
+                .map_err(|_| ErrorKind::ParseArg("rare", "an integer"))?,
+            save_dir: PathBuf::from(matches.value_of("save_dir").unwrap()),
+        })
+    }
+}
+
+// Options are layered, each overriding the last: built-in defaults, then the config file
+// (and the selected profile), then environment variables, then the command line.
+fn read_layers<F: Fn(&str) -> Option<String>>(matches: &ArgMatches, var: F) -> Result<ConfigLayer> {
+    let path = matches
+        .value_of("config")
+        .map(PathBuf::from)
+        .or_else(|| var(CONFIG_VAR).map(PathBuf::from))
+        .or_else(|| find_config_file(&var));
+    let mut file = match path {
+        Some(path) => ConfigLayer::from_file(&path)?,
+        None => ConfigLayer::default(),
+    };
+    if let Some(profile) = matches
+        .value_of("profile")
+        .map(str::to_string)
+        .or_else(|| var(PROFILE_VAR))
+    {
+        file = file.with_profile(&profile)?;
+    }
+    Ok(file
+        .merge(ConfigLayer::from_env(&var)?)
+        .merge(ConfigLayer::from_matches(matches)?))
+}
+
+// Read the options for saving snapshots of a running Game.
+fn read_checkpoint(matches: &ArgMatches) -> Result<Option<Checkpoint>> {
+    let path = match matches.value_of("checkpoint") {
+        Some(path) => PathBuf::from(path),
+        None => return Ok(None),
+    };
+    let positive = |name| match matches.value_of(name).map(str::parse) {
+        Some(Ok(n)) if n > 0 => Ok(Some(n)),
+        Some(_) => Err(ErrorKind::ParseArg(name, "an integer greater than 0")),
+        None => Ok(None),
+    };
+    let mut checkpoint = Checkpoint::new(path);
+    checkpoint.every = positive("checkpoint_every")?;
+    checkpoint.interval = positive("checkpoint_secs")?.map(Duration::from_secs);
+    Ok(Some(checkpoint))
+}
+
+// Read the options for drawing a Game as an image or an animation.
+fn read_image(matches: &ArgMatches) -> Result<Option<ImageExport>> {
+    if !matches.is_present("png") && !matches.is_present("gif") {
+        return Ok(None);
+    }
+    let mut options = ImageOptions::default();
+    if let Some(region) = matches.value_of("region") {
+        options.region = region.parse()?;
+    }
+    if let Some(size) = matches.value_of("cell_size") {
+        options.cell_size = size
+            .parse()
+            .map_err(|_| ErrorKind::ParseArg("cell_size", "an integer"))?;
+    }
+    options.gridlines = matches.is_present("gridlines");
+    let color = |name, default| match matches.value_of(name) {
+        Some(color) => color.parse(),
+        None => Ok(default),
+    };
+    options.alive = color("alive_color", options.alive)?;
+    options.dead = color("dead_color", options.dead)?;
+    options.gridline = color("gridline_color", options.gridline)?;
+    options.validate()?;
+    let integer = |name| match matches.value_of(name) {
+        Some(n) => n
+            .parse()
+            .map(Some)
+            .map_err(|_| ErrorKind::ParseArg(name, "an integer")),
+        None => Ok(None),
+    };
+    let generation = integer("generation")?;
+
+    if let Some(path) = matches.value_of("png") {
+        return Ok(Some(ImageExport::Png {
+            path: PathBuf::from(path),
+            generation,
+            options,
+        }));
+    }
+    let defaults = AnimationOptions::default();
+    let options = AnimationOptions {
+        image: options,
+        start: generation,
+        frames: integer("frames")?.unwrap_or(defaults.frames),
+        step: integer("frame_step")?.unwrap_or(defaults.step),
+        frame_delay: integer("frame_delay")?.map_or(defaults.frame_delay, Duration::from_millis),
+    };
+    options.validate()?;
+    Ok(Some(ImageExport::Gif {
+        path: PathBuf::from(matches.value_of("gif").unwrap()),
+        options,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use game::Output;
+    use image::{Color, Region};
+    use scene::PatternSource;
+    use soup::{SoupSpec, Symmetry};
+    use transform::{Flip, Rotation};
+
+    // Like `GameConfig::from_args`, but ignoring the environment and any config file.
+    fn config_from_args(args: Vec<&str>) -> Result<GameConfig> {
+        match Command::from_args_with_env(args, |_| None)? {
+            Command::Run { config, .. } => Ok(config),
+            command => bail!("expected a pattern to run, not {:?}", command),
+        }
+    }
+
+    fn mk_config(settings: Settings, bounds: (Option<u64>, Option<u64>)) -> GameConfig {
+        GameConfig {
+            settings,
+            pattern: "x.\n.x".to_string(),
+            alphabet: Alphabet::default(),
+            bounds,
+            transform: Transform::default(),
+            scene: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_build() {
+        assert!(mk_config(Settings::default(), (None, Some(4)))
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_from_args_soup() {
+        let config = config_from_args(vec!["conway", "--soup", "8x6:0.5:3:C2"]).unwrap();
+        assert_eq!(config.bounds, (Some(8), Some(6)));
+        let soup = SoupSpec::new(8, 6, 0.5, 3)
+            .with_symmetry(Symmetry::C2)
+            .generate();
+        assert_eq!(config.pattern, soup.to_string());
+        assert!(config_from_args(vec!["conway", "--soup", "8x6:0.5"]).is_err());
+    }
+
+    #[test]
+    fn test_from_args_transform() {
+        let config = config_from_args(vec![
+            "conway",
+            "--sample",
+            "glider",
+            "--rotate",
+            "90",
+            "--flip",
+            "h",
+            "--translate",
+            "(-2, 3)",
+        ])
+        .unwrap();
+        assert_eq!(
+            config.transform,
+            Transform {
+                flip: Some(Flip::Horizontal),
+                rotate: Some(Rotation::R90),
+                translate: Point(-2, 3),
+                ..Default::default()
+            }
+        );
+        let grid: Grid = config.pattern.parse().unwrap();
+        assert_eq!(
+            config.build().unwrap().grid(),
+            &grid
+                .flipped(Flip::Horizontal)
+                .rotated(Rotation::R90)
+                .translated(Point(-2, 3))
+        );
+
+        let config =
+            config_from_args(vec!["conway", "--soup", "8x6:0.5:3", "--transpose"]).unwrap();
+        assert_eq!(config.bounds, (Some(6), Some(8)));
+        assert!(config_from_args(vec!["conway", "-S", "glider", "--translate", "3"]).is_err());
+    }
+
+    #[test]
+    fn test_from_args_scene() {
+        let config = config_from_args(vec![
+            "conway",
+            "--place",
+            "sample=blinker,label=a",
+            "-P",
+            "inline=xx/xx,x=-10,y=-3",
+        ])
+        .unwrap();
+        assert_eq!(config.pattern, "");
+        assert_eq!(config.scene.len(), 2);
+        assert_eq!(config.scene[1].offset, Point(-10, -3));
+        let game = config.build().unwrap();
+        assert_eq!(game.grid().len(), 7);
+        assert!(game.grid().is_alive(&Point(-9, -2)));
+
+        assert!(config_from_args(vec!["conway", "-P", "sample=glider,x=1,x"]).is_err());
+        assert!(config_from_args(vec!["conway", "-d", "10"]).is_err());
+    }
+
+    #[test]
+    fn test_from_json_scene() {
+        let config = GameConfig::from_json(
+            r#"{
+                "bounds": [null, null],
+                "pattern": "x",
+                "scene": [
+                    {"source": {"sample": "glider"}, "offset": [5, 5], "label": "glider"},
+                    {"source": {"inline": "xxx"}, "transform": {"rotate": "90"}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.scene[1].source,
+            PatternSource::Inline("xxx".to_string())
+        );
+        let game = config.build().unwrap();
+        // The blinker overlaps the single cell at the origin.
+        assert_eq!(game.grid().len(), 8);
+
+        let config = GameConfig::from_json(
+            r#"{"bounds": [null, null], "scene": [{"source": {"library": "gun"}}]}"#,
+        )
+        .unwrap();
+        assert!(config.build().is_err());
+    }
+
+    #[test]
+    fn test_command_census() {
+        match Command::from_args_with_env(
+            vec!["conway", "census", "-n", "10", "--size", "8x4"],
+            |_| None,
+        )
+        .unwrap()
+        {
+            Command::Census { config, rare, .. } => {
+                assert_eq!(config.soups, 10);
+                assert_eq!((config.width, config.height), (8, 4));
+                assert_eq!(rare, 1);
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+        assert!(config_from_args(vec!["conway", "census"]).is_err());
+    }
+
+    #[test]
+    fn test_command_resume() {
+        let args = vec!["conway", "--resume", "game.json", "-d", "20", "-O", "json"];
+        match Command::from_args_with_env(args, |_| None).unwrap() {
+            Command::Resume {
+                path,
+                settings,
+                output,
+                checkpoint,
+                image,
+                interactive,
+            } => {
+                assert_eq!(path, PathBuf::from("game.json"));
+                assert_eq!(output.format, Output::Json);
+                assert!(checkpoint.is_none() && image.is_none() && !interactive);
+                assert_eq!(
+                    settings,
+                    SettingsUpdate {
+                        delay: Some(Duration::from_millis(20)),
+                        ..Default::default()
+                    }
+                );
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_command_checkpoint() {
+        let command = |args: &[&str]| {
+            let argv = ["conway", "-S", "glider"].iter().chain(args);
+            Command::from_args_with_env(argv, |_| None)
+        };
+        match command(&["--checkpoint", "run.json", "--checkpoint-every", "100"]).unwrap() {
+            Command::Run {
+                checkpoint: Some(checkpoint),
+                ..
+            } => {
+                assert_eq!(checkpoint.path, PathBuf::from("run.json"));
+                assert_eq!(checkpoint.every, Some(100));
+                assert_eq!(checkpoint.interval, None);
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+        let args = ["--checkpoint", "run.json", "--checkpoint-every", "0"];
+        match command(&args).unwrap_err().kind() {
+            ErrorKind::ParseArg(arg, _) => assert_eq!(*arg, "checkpoint_every"),
+            kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn test_command_image() {
+        let args = vec![
+            "conway",
+            "-S",
+            "glider",
+            "--png",
+            "glider.png",
+            "--generation",
+            "4",
+            "--region",
+            "8x8@-2,-2",
+            "--cell-size",
+            "16",
+            "--gridlines",
+            "--alive-color",
+            "#ff0000",
+        ];
+        match Command::from_args_with_env(args, |_| None).unwrap() {
+            Command::Run {
+                image: Some(image), ..
+            } => assert_eq!(
+                image,
+                ImageExport::Png {
+                    path: PathBuf::from("glider.png"),
+                    generation: Some(4),
+                    options: ImageOptions {
+                        cell_size: 16,
+                        gridlines: true,
+                        alive: Color(0xff, 0, 0),
+                        region: Region::Rect(Point(-2, -2), Point(5, 5)),
+                        ..ImageOptions::default()
+                    },
                 }
-            },
-            bounds: (
-                matches
-                    .value_of("width")
-                    .and_then(|s| if s == "auto" { None } else { Some(s.parse()) })
-                    .transpose()
-                    .map_err(|_| ErrorKind::ParseArg("width", "an integer"))?,
-                matches
-                    .value_of("height")
-                    .and_then(|s| if s == "auto" { None } else { Some(s.parse()) })
-                    .transpose()
-                    .map_err(|_| ErrorKind::ParseArg("height", "an integer"))?,
             ),
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_command_gif() {
+        let args = vec![
+            "conway",
+            "-S",
+            "glider",
+            "--gif",
+            "glider.gif",
+            "--frames",
+            "20",
+            "--frame-step",
+            "2",
+            "--frame-delay",
+            "50",
+            "--region",
+            "viewport",
+        ];
+        match Command::from_args_with_env(args, |_| None).unwrap() {
+            Command::Run {
+                image: Some(image), ..
+            } => assert_eq!(
+                image,
+                ImageExport::Gif {
+                    path: PathBuf::from("glider.gif"),
+                    options: AnimationOptions {
+                        image: ImageOptions {
+                            region: Region::Viewport,
+                            ..ImageOptions::default()
+                        },
+                        start: None,
+                        frames: 20,
+                        step: 2,
+                        frame_delay: Duration::from_millis(50),
+                    },
+                }
+            ),
+            command => panic!("unexpected command: {:?}", command),
+        }
+        let args = vec![
+            "conway",
+            "-S",
+            "glider",
+            "--gif",
+            "glider.gif",
+            "--frames",
+            "0",
+        ];
+        match Command::from_args_with_env(args, |_| None)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::InvalidConfig(field, _) => assert_eq!(*field, "frames"),
+            kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let invalid_field = |config: GameConfig| match config.build().unwrap_err().kind() {
+            ErrorKind::InvalidConfig(field, _) => *field,
+            kind => panic!("unexpected error: {}", kind),
         };
+        assert_eq!(
+            invalid_field(mk_config(Settings::default(), (Some(0), None))),
+            "width"
+        );
+        assert_eq!(
+            invalid_field(mk_config(
+                Settings {
+                    char_dead: *CHAR_ALIVE,
+                    ..Default::default()
+                },
+                (None, None)
+            )),
+            "char_dead"
+        );
+        assert_eq!(
+            invalid_field(mk_config(
+                Settings {
+                    char_alive: '\n',
+                    ..Default::default()
+                },
+                (None, None)
+            )),
+            "char_alive"
+        );
+        assert_eq!(
+            invalid_field(mk_config(
+                Settings {
+                    view: View::Follow,
+                    ..Default::default()
+                },
+                (None, None)
+            )),
+            "view"
+        );
 
-        Ok(conf)
+        let at = |x: i64| Placement {
+            offset: Point(x, 0),
+            ..Placement::new(PatternSource::Inline("x".to_string()))
+        };
+        let mut config = mk_config(Settings::default(), (None, None));
+        config.scene = vec![at(i64::MIN), at(i64::MAX)];
+        assert_eq!(invalid_field(config), "scene");
     }
 }
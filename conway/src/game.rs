@@ -1,13 +1,22 @@
+use std::collections::VecDeque;
 use std::fmt;
+use std::fs;
 use std::mem;
+use std::path::Path;
 use std::str::FromStr;
 use std::thread;
+use std::time::Instant;
 
 use num_integer::Integer;
 
 pub use config::Settings;
+use config::SettingsUpdate;
 use grid::{Grid, Point};
-use {Error, Result};
+use history::History;
+use image::{Image, ImageOptions};
+use serde_json;
+use stats::Stats;
+use {Error, ErrorKind, Result, ResultExt};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -44,7 +53,61 @@ impl fmt::Display for View {
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// How a Game is rendered by `Game::render`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    /// A grid of cells, optionally followed by a line of Stats.
+    #[default]
+    Text,
+    /// One line of json per turn, holding the grid and its Stats.
+    Json,
+}
+
+impl FromStr for Output {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Output::Text),
+            "json" => Ok(Output::Json),
+            s => bail!("invalid value for output '{}'", s),
+        }
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Output::Text => "text",
+                Output::Json => "json",
+            }
+        )
+    }
+}
+
+/// How `Game::render` prints each turn. These are options of the command line program rather than
+/// Settings, so they're never sent to clients or saved with a Game.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputOptions {
+    pub format: Output,
+    pub show_stats: bool,
+}
+
+/// A single rendered turn, for json output.
+#[derive(Serialize)]
+struct Frame<'a> {
+    grid: Vec<&'a str>,
+    stats: &'a Stats,
+}
+
+/// The maximum number of Stats records that a Game keeps.
+pub const STATS_CAPACITY: usize = 1000;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Viewport {
     pub origin: Point,
     pub scroll: Point,
@@ -66,7 +129,10 @@ impl Viewport {
     pub fn centered(&self, Point(x, y): Point) -> (Point, Point) {
         let (dx0, dx1) = split_int(self.width as i64);
         let (dy0, dy1) = split_int(self.height as i64);
-        (Point(x - dx0, y - dy0), Point(x + dx1 - 1, y + dy1 - 1))
+        (
+            Point(x.saturating_sub(dx0), y.saturating_sub(dy0)),
+            Point(x.saturating_add(dx1 - 1), y.saturating_add(dy1 - 1)),
+        )
     }
 
     pub fn center(&mut self, point: Point) {
@@ -82,6 +148,7 @@ impl Viewport {
 pub struct GameIter<'a> {
     game: &'a mut Game,
     with_delay: bool,
+    output: OutputOptions,
 }
 
 impl<'a> GameIter<'a> {
@@ -89,6 +156,16 @@ impl<'a> GameIter<'a> {
         self.with_delay = with_delay;
         self
     }
+
+    pub fn with_output(mut self, output: OutputOptions) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Return the Game as of the last turn.
+    pub fn game(&self) -> &Game {
+        self.game
+    }
 }
 
 impl<'a> Iterator for GameIter<'a> {
@@ -103,16 +180,22 @@ impl<'a> Iterator for GameIter<'a> {
             } else {
                 self.game.tick();
             }
-            Some(self.game.draw())
+            Some(self.game.render(&self.output))
         }
     }
 }
 
 /// Game holds the high-level gameplay logic.
-#[derive(Debug, Clone)]
+///
+/// A Game can be serialized as a snapshot of its whole state, to be picked up again later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Game {
     grid: Grid,
+    #[serde(skip)]
     swap: Grid,
+    generation: u64,
+    history: History,
+    stats: VecDeque<Stats>,
     pub opts: Settings,
     pub viewport: Viewport,
 }
@@ -131,9 +214,13 @@ impl Game {
             scroll: Point::origin(),
         };
 
+        let stats = vec![Stats::initial(0, &grid)].into_iter().collect();
         let mut game = Game {
             grid,
             swap,
+            generation: 0,
+            history: History::default(),
+            stats,
             opts,
             viewport,
         };
@@ -141,6 +228,71 @@ impl Game {
         game
     }
 
+    /// Restore a Game that was previously running, e.g. from a saved copy of its state.
+    ///
+    /// Unlike `new`, the viewport is used as-is rather than being fit around the grid.
+    pub fn restore(grid: Grid, generation: u64, opts: Settings, viewport: Viewport) -> Game {
+        let stats = vec![Stats::initial(generation, &grid)]
+            .into_iter()
+            .collect();
+        Game {
+            grid,
+            swap: Grid::empty(),
+            generation,
+            history: History::default(),
+            stats,
+            opts,
+            viewport,
+        }
+    }
+
+    /// Load a Game from a snapshot file written by `save`.
+    pub fn load(path: &Path) -> Result<Game> {
+        let json = fs::read_to_string(path)
+            .chain_err(|| format!("failed to read snapshot {}", path.display()))?;
+        let mut game: Game = serde_json::from_str(&json)
+            .chain_err(|| format!("failed to read snapshot {}", path.display()))?;
+        game.validate()
+            .chain_err(|| format!("invalid snapshot {}", path.display()))?;
+        if game.stats.is_empty() {
+            game.stats
+                .push_back(Stats::initial(game.generation, &game.grid));
+        }
+        Ok(game)
+    }
+
+    // Check the parts of a loaded snapshot that serde can't.
+    fn validate(&self) -> Result<()> {
+        self.opts.validate()?;
+        self.history.validate()?;
+        if self.viewport.width == 0 || self.viewport.height == 0 {
+            bail!(ErrorKind::InvalidConfig(
+                "viewport",
+                "must be at least 1x1".to_string()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Save a snapshot of the Game to a file, as json.
+    ///
+    /// The snapshot is written to a temporary file first and then moved into place, so an
+    /// existing snapshot is never left half-written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let json = serde_json::to_string(self).chain_err(|| "failed to serialize the game")?;
+        fs::write(&tmp, json)
+            .and_then(|_| fs::rename(&tmp, path))
+            .chain_err(|| format!("failed to write snapshot {}", path.display()))
+    }
+
+    /// Use the given History to record past generations, instead of the default.
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = history;
+        self
+    }
+
     /// Return an iterator over the turns of the Game, returning the rendered output of each turn.
     ///
     /// Iteration finishes when the Game is over.
@@ -148,6 +300,7 @@ impl Game {
         GameIter {
             game: self,
             with_delay: false,
+            output: OutputOptions::default(),
         }
     }
 
@@ -155,13 +308,73 @@ impl Game {
     ///
     /// Applies the rules of game to each individual cell, killing some and reviving others.
     pub fn tick(&mut self) {
+        let started = Instant::now();
+        self.history.record(self.generation, &self.grid);
+        let (mut births, mut deaths) = (0, 0);
         for cell in self.grid.active_cells() {
+            let alive = self.grid.is_alive(&cell);
             if self.survives(&cell) {
                 self.swap.set_alive(cell);
+                if !alive {
+                    births += 1;
+                }
+            } else if alive {
+                deaths += 1;
             }
         }
         self.grid.clear();
         mem::swap(&mut self.grid, &mut self.swap);
+        self.generation += 1;
+
+        if self.stats.len() == STATS_CAPACITY {
+            self.stats.pop_front();
+        }
+        self.stats.push_back(Stats::new(
+            self.generation,
+            &self.grid,
+            births,
+            deaths,
+            started.elapsed(),
+        ));
+    }
+
+    /// Go back `n` generations, or as far back as the history allows if `n` is past the start.
+    pub fn step_back(&mut self, n: u64) -> Result<()> {
+        let target = self.generation.saturating_sub(n);
+        let target = self
+            .history
+            .earliest()
+            .map_or(target, |earliest| target.max(earliest));
+        self.seek(target)
+    }
+
+    /// Move the Game to the given generation.
+    ///
+    /// Earlier generations are recovered from the nearest keyframe in the history and then
+    /// re-simulated, so this fails if the generation is older than the history goes back.
+    pub fn seek(&mut self, generation: u64) -> Result<()> {
+        if generation < self.generation {
+            let (keyframe, grid) = match self.history.keyframe(generation) {
+                Some((keyframe, grid)) => (keyframe, grid.clone()),
+                None => bail!(ErrorKind::HistoryUnavailable(generation)),
+            };
+            self.history.truncate(generation);
+            while let Some(stats) = self.stats.pop_back() {
+                if stats.generation <= keyframe {
+                    self.stats.push_back(stats);
+                    break;
+                }
+            }
+            if self.stats.is_empty() {
+                self.stats.push_back(Stats::initial(keyframe, &grid));
+            }
+            self.grid = grid;
+            self.generation = keyframe;
+        }
+        while self.generation < generation {
+            self.tick();
+        }
+        Ok(())
     }
 
     /// Call `tick`, then sleep for `self.opts.delay`.
@@ -175,6 +388,28 @@ impl Game {
         self.draw_viewport(self.viewport())
     }
 
+    /// Draw the Game as an Image.
+    pub fn to_image(&self, opts: &ImageOptions) -> Result<Image> {
+        let bounds = opts.region.bounds(&self.grid, Some(self.viewport()));
+        Image::draw(&self.grid, bounds, opts)
+    }
+
+    /// Render the Game in the given output format.
+    pub fn render(&self, opts: &OutputOptions) -> String {
+        match opts.format {
+            Output::Text if opts.show_stats => format!("{}{}\n", self.draw(), self.stats()),
+            Output::Text => self.draw(),
+            Output::Json => {
+                let grid = self.draw();
+                let frame = Frame {
+                    grid: grid.lines().collect(),
+                    stats: self.stats(),
+                };
+                format!("{}\n", serde_json::to_string(&frame).unwrap())
+            }
+        }
+    }
+
     /// Render the section of the Game, within the given bounds, as a grid of cells.
     fn draw_viewport(&self, (Point(x0, y0), Point(x1, y1)): (Point, Point)) -> String {
         let mut output = String::new();
@@ -191,6 +426,30 @@ impl Game {
         output
     }
 
+    /// Apply a partial update to the Game's Settings. Nothing changes if the result is invalid.
+    pub fn update_settings(&mut self, update: &SettingsUpdate) -> Result<()> {
+        let opts = update.apply(&self.opts);
+        opts.validate()?;
+        self.opts = opts;
+        Ok(())
+    }
+
+    /// Change the size of the Game's viewport, keeping it centered on the same point.
+    pub fn resize(&mut self, width: u64, height: u64) -> Result<()> {
+        if width == 0 || height == 0 {
+            bail!(ErrorKind::InvalidConfig(
+                "bounds",
+                "must be greater than 0".to_string()
+            ));
+        }
+        let (Point(x0, y0), Point(x1, y1)) = self.viewport.bounds();
+        let center = Point(x0 + (x1 - x0 + 1) / 2, y0 + (y1 - y0 + 1) / 2);
+        self.viewport.width = width;
+        self.viewport.height = height;
+        self.viewport.center(center);
+        Ok(())
+    }
+
     /// Shift the Game's viewport by the given x and y deltas.
     pub fn scroll(&mut self, dx: i64, dy: i64) {
         self.viewport.scroll += Point(dx, dy);
@@ -210,6 +469,26 @@ impl Game {
         }
     }
 
+    /// Return the current state of the grid.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// Return the number of turns that have been executed since the Game began.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Return the Stats for the most recent turn.
+    pub fn stats(&self) -> &Stats {
+        self.stats.back().expect("stats should never be empty")
+    }
+
+    /// Return the Stats for recent turns, oldest first, up to `STATS_CAPACITY` of them.
+    pub fn stats_history(&self) -> impl Iterator<Item = &Stats> {
+        self.stats.iter()
+    }
+
     /// Return whether the Game is over (true if the grid is empty, else false).
     // TODO: make this `is_stablized` and increase functionality.
     pub fn is_over(&self) -> bool {
@@ -248,6 +527,16 @@ mod test {
         Game::new(Grid::new(cells), Settings::default(), bounds)
     }
 
+    // Patterns at the edges of the plane shouldn't overflow when the viewport is fit around them.
+    #[test]
+    fn test_new_at_edges() {
+        for &x in &[i64::MIN, i64::MAX] {
+            let game = mk_game(vec![Point(x, 0)], (None, None));
+            assert_eq!((game.viewport.width, game.viewport.height), (1, 1));
+            mk_game(vec![Point(x, x)], (Some(8), Some(8)));
+        }
+    }
+
     // Viewport width/height should be taken from Settings if given.
     #[test]
     fn test_size_provided() {
@@ -264,6 +553,191 @@ mod test {
         assert_eq!(game.viewport.height, 6);
     }
 
+    // Each tick should advance the generation, and `restore` should pick up where it left off.
+    #[test]
+    fn test_generation() {
+        let mut game = mk_game(vec![Point(1, 0), Point(1, 1), Point(1, 2)], (None, None));
+        assert_eq!(game.generation(), 0);
+        game.tick();
+        game.tick();
+        assert_eq!(game.generation(), 2);
+
+        let restored = Game::restore(
+            game.grid().clone(),
+            game.generation(),
+            game.opts.clone(),
+            game.viewport.clone(),
+        );
+        assert_eq!(restored.generation(), 2);
+        assert_eq!(restored.grid(), game.grid());
+        assert_eq!(restored.draw(), game.draw());
+    }
+
+    // Stepping back should recover earlier generations by re-simulating from a keyframe.
+    #[test]
+    fn test_step_back() {
+        let glider = vec![
+            Point(1, 0),
+            Point(2, 1),
+            Point(0, 2),
+            Point(1, 2),
+            Point(2, 2),
+        ];
+        let mut game = mk_game(glider.clone(), (None, None)).with_history(History::new(4, 2));
+        let mut grids = vec![game.grid().clone()];
+        for _ in 0..10 {
+            game.tick();
+            grids.push(game.grid().clone());
+        }
+
+        game.step_back(3).unwrap();
+        assert_eq!(game.generation(), 7);
+        assert_eq!(game.grid(), &grids[7]);
+
+        game.seek(9).unwrap();
+        assert_eq!(game.generation(), 9);
+        assert_eq!(game.grid(), &grids[9]);
+
+        assert!(
+            game.seek(2).is_err(),
+            "should not seek past the oldest keyframe"
+        );
+        assert_eq!(
+            game.generation(),
+            9,
+            "failed seeks should not change the game"
+        );
+
+        game.step_back(100).unwrap();
+        assert_eq!(game.generation(), 4, "should stop at the oldest keyframe");
+        assert_eq!(game.grid(), &grids[4]);
+    }
+
+    // A snapshot should carry on exactly where the Game left off, history included.
+    #[test]
+    fn test_snapshot() {
+        let glider = vec![
+            Point(1, 0),
+            Point(2, 1),
+            Point(0, 2),
+            Point(1, 2),
+            Point(2, 2),
+        ];
+        let mut game = mk_game(glider, (Some(8), Some(8))).with_history(History::new(2, 4));
+        for _ in 0..6 {
+            game.tick();
+        }
+        game.scroll(3, -2);
+
+        let path = ::std::env::temp_dir().join(format!("conway-snapshot-{}", ::std::process::id()));
+        game.save(&path).unwrap();
+        let mut restored = Game::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.generation(), 6);
+        assert_eq!(restored.grid(), game.grid());
+        assert_eq!(restored.viewport, game.viewport);
+        assert_eq!(restored.opts, game.opts);
+        assert_eq!(
+            restored.stats_history().collect::<Vec<_>>(),
+            game.stats_history().collect::<Vec<_>>()
+        );
+        restored.step_back(4).unwrap();
+        game.step_back(4).unwrap();
+        assert_eq!(restored.grid(), game.grid());
+        restored.tick();
+        game.tick();
+        assert_eq!(restored.draw(), game.draw());
+
+        assert!(Game::load(&path).is_err());
+
+        // Snapshots that break the Game's invariants are rejected rather than loaded.
+        let json = serde_json::to_string(&game).unwrap();
+        for (from, to) in &[
+            (r#""interval":2"#, r#""interval":0"#),
+            (r#""view":"centered""#, r#""view":"follow""#),
+            (r#""width":8"#, r#""width":0"#),
+        ] {
+            assert!(json.contains(from), "{}", from);
+            fs::write(&path, json.replace(from, to)).unwrap();
+            assert!(Game::load(&path).is_err(), "{}", to);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    // Each turn should record births, deaths and population.
+    #[test]
+    fn test_stats() {
+        let mut game = mk_game(vec![Point(1, 0), Point(1, 1), Point(1, 2)], (None, None));
+        assert_eq!(game.stats().population, 3);
+        game.tick();
+        let stats = game.stats().clone();
+        assert_eq!(stats.generation, 1);
+        assert_eq!(stats.population, 3);
+        assert_eq!((stats.births, stats.deaths), (2, 2));
+        assert_eq!(stats.bounds, Some((Point(0, 1), Point(2, 1))));
+
+        game.tick();
+        game.step_back(1).unwrap();
+        assert_eq!(
+            game.stats_history()
+                .map(|stats| stats.generation)
+                .collect::<Vec<_>>(),
+            vec![0, 1],
+            "stepping back should forget later stats"
+        );
+    }
+
+    // The drawn Game should parse back into its Grid, with the Alphabet from its Settings.
+    #[test]
+    fn test_draw_round_trip() {
+        let cells = vec![
+            Point(1, 0),
+            Point(2, 1),
+            Point(0, 2),
+            Point(1, 2),
+            Point(2, 2),
+        ];
+        for &(alive, dead) in &[('#', '-'), ('■', '□'), ('x', '.')] {
+            let settings = Settings {
+                char_alive: alive,
+                char_dead: dead,
+                ..Default::default()
+            };
+            let game = Game::new(Grid::new(cells.clone()), settings.clone(), (None, None));
+            assert_eq!(
+                Grid::parse_with(&game.draw(), &settings.alphabet()).unwrap(),
+                game.grid
+            );
+        }
+    }
+
+    // Test `Game.update_settings`.
+    #[test]
+    fn test_update_settings() {
+        let mut game = mk_game(vec![Point(0, 0)], (None, None));
+        game.update_settings(&SettingsUpdate {
+            char_alive: Some('o'),
+            view: Some(View::Fixed),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(game.opts.char_alive, 'o');
+        assert_eq!(game.opts.view, View::Fixed);
+        assert_eq!(game.opts.char_dead, Settings::default().char_dead);
+
+        let opts = game.opts.clone();
+        assert!(
+            game.update_settings(&SettingsUpdate {
+                char_dead: Some('o'),
+                ..Default::default()
+            })
+            .is_err(),
+            "invalid updates should be rejected"
+        );
+        assert_eq!(game.opts, opts, "invalid updates should not be applied");
+    }
+
     // Test `Game.survive`.
     #[test]
     fn test_survives() {
@@ -436,6 +910,19 @@ mod test {
             assert_eq!(game.viewport.bounds(), expected);
         }
 
+        // `Game.resize` should keep the viewport centered on the same point.
+        #[test]
+        fn test_resize() {
+            let mut game = mk_game(vec![Point(0, 0), Point(9, 9)], (Some(10), Some(10)));
+            game.scroll(3, -2);
+            assert_eq!(game.viewport.bounds(), (Point(3, -2), Point(12, 7)));
+            game.resize(4, 6).unwrap();
+            assert_eq!(game.viewport.bounds(), (Point(6, 0), Point(9, 5)));
+            game.resize(20, 10).unwrap();
+            assert_eq!(game.viewport.bounds(), (Point(-2, -2), Point(17, 7)));
+            assert!(game.resize(0, 10).is_err());
+        }
+
         // `Game.center_viewport` should account for current scroll.
         #[test]
         fn test_center_viewport_with_scroll() {
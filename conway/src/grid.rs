@@ -1,13 +1,43 @@
 use std::collections::HashSet;
+use std::fmt;
+use std::result;
 use std::str::FromStr;
 
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use alphabet::Alphabet;
+use image::{Image, ImageOptions};
+use pattern::Pattern;
 pub use point::Point;
-use {Error, ErrorKind, Result};
+use soup::SoupSpec;
+use transform::{Flip, Rotation, Transform};
+use {Error, Result, ResultExt};
 
 pub const READ_CHAR_ALIVE: char = 'x';
 pub const READ_CHAR_DEAD: char = '.';
 pub const COMMENT_CHAR: char = '#';
 
+/// The most live cells that a run-length encoded Grid can be decoded into.
+pub const MAX_DECODED_CELLS: usize = 1 << 24;
+
+// Digits used by the Wechsler format, in which each digit encodes a 5-cell column.
+const WECHSLER_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuv";
+// Digits used to encode the length of a run of 4 or more zeros, after a 'y'.
+const ZERO_RUN_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+// The 8 rotations and reflections of the plane.
+static ORIENTATIONS: [fn(Point) -> Point; 8] = [
+    |Point(x, y)| Point(x, y),
+    |Point(x, y)| Point(-y, x),
+    |Point(x, y)| Point(-x, -y),
+    |Point(x, y)| Point(y, -x),
+    |Point(x, y)| Point(-x, y),
+    |Point(x, y)| Point(x, -y),
+    |Point(x, y)| Point(y, x),
+    |Point(x, y)| Point(-y, -x),
+];
+
 static DIRECTIONS: &'static [(i64, i64)] = &[
     (-1, -1),
     (-1, 0),
@@ -19,6 +49,27 @@ static DIRECTIONS: &'static [(i64, i64)] = &[
     (1, 1),
 ];
 
+/// A Cluster is a group of live cells that `Grid::clusters` separated from the rest of a Grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    /// The lowest and highest X and Y coordinates of the cells in the Cluster.
+    pub bounds: (Point, Point),
+    /// The cells in the Cluster, at the same positions as in the original Grid.
+    pub cells: Grid,
+}
+
+/// How `Grid::stamp` combines a pattern with the cells already in a Grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StampMode {
+    /// Replace everything inside the pattern's bounding box with the pattern.
+    Overwrite,
+    /// Bring the pattern's cells to life, and leave the rest alone.
+    Or,
+    /// Flip the state of every cell under one of the pattern's cells.
+    Xor,
+}
+
 /// A Grid represents the physical world in which Conway's Game of Life takes place.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Grid {
@@ -40,6 +91,16 @@ impl Grid {
         }
     }
 
+    /// Create a random Grid of the given size, where each cell is alive with the given
+    /// probability. The same seed always gives the same Grid.
+    ///
+    /// Fails if the size is zero or the density isn't between 0 and 1.
+    pub fn random(width: u64, height: u64, density: f64, seed: u64) -> Result<Self> {
+        let spec = SoupSpec::new(width, height, density, seed);
+        spec.validate()?;
+        Ok(spec.generate())
+    }
+
     /*
      * Points
      */
@@ -73,11 +134,21 @@ impl Grid {
             .collect()
     }
 
+    /// Return an iterator over the living cells in the Grid, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Point> {
+        self.cells.iter()
+    }
+
     /// Return whether the Grid is empty.
     pub fn is_empty(&self) -> bool {
         self.cells.is_empty()
     }
 
+    /// Return the number of living cells in the Grid.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
     /// Return whether the cell at the given Point is alive.
     pub fn is_alive(&self, cell: &Point) -> bool {
         self.cells.contains(cell)
@@ -105,7 +176,9 @@ impl Grid {
     /// Return the Point closest to the center of the Grid.
     pub fn midpoint(&self) -> Point {
         let (Point(x0, y0), Point(x1, y1)) = self.bounds();
-        Point((x0 + x1 + 1) / 2, (y0 + y1 + 1) / 2)
+        // Widen so that cells near the edges of the plane don't overflow.
+        let mid = |a: i64, b: i64| ((i128::from(a) + i128::from(b) + 1) / 2) as i64;
+        Point(mid(x0, x1), mid(y0, y1))
     }
 
     // Return the lowest and highest X and Y coordinates represented in the Grid.
@@ -130,43 +203,491 @@ impl Grid {
             (Point::origin(), Point::origin())
         }
     }
+
+    /*
+     * Text
+     */
+
+    /// Parse a Grid from text written with the given Alphabet.
+    pub fn parse_with(s: &str, alphabet: &Alphabet) -> Result<Grid> {
+        Ok(Pattern::parse_with(s, alphabet)?.placed())
+    }
+
+    /// Write the Grid as rows of cells with the given Alphabet, from its top-left corner.
+    pub fn to_string_with(&self, alphabet: &Alphabet) -> String {
+        let mut s = String::new();
+        if self.is_empty() {
+            return s;
+        }
+        let (Point(x0, y0), Point(x1, y1)) = self.bounds();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                s.push(if self.is_alive(&Point(x, y)) {
+                    alphabet.alive_char()
+                } else {
+                    alphabet.dead_char()
+                });
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Draw the Grid as an Image.
+    pub fn to_image(&self, opts: &ImageOptions) -> Result<Image> {
+        Image::draw(self, opts.region.bounds(self, None), opts)
+    }
+
+    /*
+     * Combining
+     */
+
+    /// Return a Grid with the cells that are alive in either Grid.
+    pub fn union(&self, other: &Grid) -> Grid {
+        Grid::new(self.cells.union(&other.cells).cloned())
+    }
+
+    /// Return a Grid with the cells that are alive in both Grids.
+    pub fn intersection(&self, other: &Grid) -> Grid {
+        Grid::new(self.cells.intersection(&other.cells).cloned())
+    }
+
+    /// Return a Grid with the cells that are alive in this Grid but not in `other`.
+    pub fn difference(&self, other: &Grid) -> Grid {
+        Grid::new(self.cells.difference(&other.cells).cloned())
+    }
+
+    /// Return a Grid with the cells that are alive in exactly one of the Grids.
+    pub fn xor(&self, other: &Grid) -> Grid {
+        Grid::new(self.cells.symmetric_difference(&other.cells).cloned())
+    }
+
+    /// Stamp the pattern in `other`, moved by `offset`, onto the Grid.
+    pub fn stamp(&mut self, other: &Grid, offset: Point, mode: StampMode) {
+        if other.is_empty() {
+            return;
+        }
+        match mode {
+            StampMode::Overwrite => {
+                let (p0, p1) = other.bounds();
+                let (Point(x0, y0), Point(x1, y1)) = (p0 + offset, p1 + offset);
+                self.cells
+                    .retain(|&Point(x, y)| x < x0 || x > x1 || y < y0 || y > y1);
+                self.cells.extend(other.iter().map(|&cell| cell + offset));
+            }
+            StampMode::Or => self.cells.extend(other.iter().map(|&cell| cell + offset)),
+            StampMode::Xor => {
+                for &cell in other.iter() {
+                    let cell = cell + offset;
+                    if !self.cells.remove(&cell) {
+                        self.cells.insert(cell);
+                    }
+                }
+            }
+        }
+    }
+
+    /*
+     * Transforms
+     *
+     * Rotating, flipping and transposing keep the top-left corner of the Grid where it is.
+     */
+
+    /// Move every cell in the Grid by the given offset.
+    pub fn translate(&mut self, by: Point) {
+        if by != Point::origin() {
+            self.cells = self.cells.iter().map(|&cell| cell.translated(by)).collect();
+        }
+    }
+
+    /// Return a copy of the Grid with every cell moved by the given offset, or `None` if that
+    /// would move any cell out of range.
+    pub fn checked_translated(&self, by: Point) -> Option<Grid> {
+        let cells = self
+            .cells
+            .iter()
+            .map(|&cell| cell.checked_add(by))
+            .collect::<Option<_>>()?;
+        Some(Grid { cells })
+    }
+
+    /// Return a copy of the Grid with every cell moved by the given offset.
+    pub fn translated(&self, by: Point) -> Grid {
+        let mut grid = self.clone();
+        grid.translate(by);
+        grid
+    }
+
+    /// Rotate the Grid clockwise.
+    pub fn rotate(&mut self, rotation: Rotation) {
+        self.reorient(|cell| cell.rotated(rotation));
+    }
+
+    /// Return a copy of the Grid rotated clockwise.
+    pub fn rotated(&self, rotation: Rotation) -> Grid {
+        let mut grid = self.clone();
+        grid.rotate(rotation);
+        grid
+    }
+
+    /// Mirror the Grid left to right (`Horizontal`) or top to bottom (`Vertical`).
+    pub fn flip(&mut self, flip: Flip) {
+        self.reorient(|cell| cell.flipped(flip));
+    }
+
+    /// Return a mirrored copy of the Grid.
+    pub fn flipped(&self, flip: Flip) -> Grid {
+        let mut grid = self.clone();
+        grid.flip(flip);
+        grid
+    }
+
+    /// Mirror the Grid across its top-left to bottom-right diagonal.
+    pub fn transpose(&mut self) {
+        self.reorient(Point::transposed);
+    }
+
+    /// Return a copy of the Grid mirrored across its top-left to bottom-right diagonal.
+    pub fn transposed(&self) -> Grid {
+        let mut grid = self.clone();
+        grid.transpose();
+        grid
+    }
+
+    /// Apply a Transform to the Grid.
+    pub fn transform(&mut self, transform: &Transform) {
+        transform.apply(self);
+    }
+
+    /// Return a copy of the Grid with a Transform applied.
+    pub fn transformed(&self, transform: &Transform) -> Grid {
+        let mut grid = self.clone();
+        grid.transform(transform);
+        grid
+    }
+
+    // Map every cell through `f`, then move the result back to the Grid's original top-left
+    // corner.
+    fn reorient<F: Fn(Point) -> Point>(&mut self, f: F) {
+        if self.is_empty() {
+            return;
+        }
+        let (corner, _) = self.bounds();
+        self.cells = self.cells.iter().map(|&cell| f(cell)).collect();
+        let (new_corner, _) = self.bounds();
+        self.translate(corner - new_corner);
+    }
+
+    /*
+     * Objects
+     */
+
+    /// Split the Grid into Clusters of live cells, where each cell is at most `distance` cells
+    /// away (horizontally, vertically or diagonally) from another cell in the same Cluster.
+    ///
+    /// A `distance` of 1 gives groups of touching cells, and a `distance` of 2 groups cells that
+    /// can affect each other's next generation. Clusters are ordered by their top-left corner.
+    pub fn clusters(&self, distance: u64) -> Vec<Cluster> {
+        let distance = distance as i64;
+        let mut unvisited: HashSet<Point> = self.cells.clone();
+        let mut clusters = Vec::new();
+        while let Some(&start) = unvisited.iter().next() {
+            unvisited.remove(&start);
+            let mut cells = vec![start];
+            let mut i = 0;
+            while i < cells.len() {
+                let Point(x, y) = cells[i];
+                for dy in -distance..=distance {
+                    for dx in -distance..=distance {
+                        if unvisited.remove(&Point(x + dx, y + dy)) {
+                            cells.push(Point(x + dx, y + dy));
+                        }
+                    }
+                }
+                i += 1;
+            }
+            let cells = Grid::new(cells);
+            clusters.push(Cluster {
+                bounds: cells.bounds(),
+                cells,
+            });
+        }
+        clusters.sort_by_key(|cluster| {
+            let (Point(x0, y0), Point(x1, y1)) = cluster.bounds;
+            (y0, x0, y1, x1)
+        });
+        clusters
+    }
+
+    /*
+     * Canonical forms
+     */
+
+    /// Return a copy of the Grid, translated so that its top-left corner is at the origin.
+    pub fn at_origin(&self) -> Grid {
+        let (origin, _) = self.bounds();
+        Grid::new(self.cells.iter().map(|&cell| cell - origin))
+    }
+
+    /// Return the canonical form of the Grid's pattern: of its 8 rotations and reflections,
+    /// translated to the origin, the one whose cells come first when sorted by row then column.
+    ///
+    /// Two Grids have the same normalized form if and only if they hold the same pattern, in any
+    /// position and orientation.
+    pub fn normalized(&self) -> Grid {
+        Grid::new(
+            self.orientations()
+                .into_iter()
+                .min_by_key(|cells| cells.iter().map(|&Point(x, y)| (y, x)).collect::<Vec<_>>())
+                .unwrap_or_else(Vec::new),
+        )
+    }
+
+    /// Return a hash of the Grid's normalized form, which is the same for every position and
+    /// orientation of a pattern, and across runs and versions of the program.
+    pub fn content_hash(&self) -> u64 {
+        // 64-bit FNV-1a.
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for Point(x, y) in sorted_cells(&self.normalized()) {
+            for byte in x.to_le_bytes().iter().chain(y.to_le_bytes().iter()) {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        hash
+    }
+
+    /// Encode the Grid's pattern in extended Wechsler format, as used in the body of an apgcode
+    /// (e.g. `33` for a block). Of the encodings of its 8 orientations, the shortest is returned,
+    /// with ties broken alphabetically, so every position and orientation gives the same code.
+    pub fn wechsler(&self) -> String {
+        self.orientations()
+            .iter()
+            .map(|cells| wechsler(cells))
+            .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+            .unwrap_or_default()
+    }
+
+    // Return the Grid's cells in each of their 8 orientations, translated to the origin and
+    // sorted by row then column.
+    fn orientations(&self) -> Vec<Vec<Point>> {
+        ORIENTATIONS
+            .iter()
+            .map(|orient| {
+                sorted_cells(&Grid::new(self.cells.iter().map(|&p| orient(p))).at_origin())
+            })
+            .collect()
+    }
+}
+
+// Return the Grid's cells sorted by row then column.
+fn sorted_cells(grid: &Grid) -> Vec<Point> {
+    let mut cells: Vec<Point> = grid.cells.iter().cloned().collect();
+    cells.sort_by_key(|&Point(x, y)| (y, x));
+    cells
+}
+
+// Encode cells at the origin in extended Wechsler format. The pattern is cut into strips 5 cells
+// high, separated by 'z', and each column of a strip is written as one digit. Runs of zeros are
+// shortened to 'w' (2), 'x' (3) or 'y' followed by a digit (4 to 39).
+fn wechsler(cells: &[Point]) -> String {
+    let alive: HashSet<Point> = cells.iter().cloned().collect();
+    let width = cells.iter().map(|p| p.0 + 1).max().unwrap_or(0);
+    let height = cells.iter().map(|p| p.1 + 1).max().unwrap_or(0);
+    let mut code = String::new();
+    for strip in 0..(height + 4) / 5 {
+        if strip > 0 {
+            code.push('z');
+        }
+        let digits: String = (0..width)
+            .map(|x| {
+                let value = (0..5)
+                    .filter(|&bit| alive.contains(&Point(x, strip * 5 + bit)))
+                    .fold(0, |value, bit| value | 1 << bit);
+                WECHSLER_DIGITS[value] as char
+            })
+            .collect();
+        push_zero_runs(&mut code, digits.trim_end_matches('0'));
+    }
+    code
+}
+
+fn push_zero_runs(code: &mut String, digits: &str) {
+    let mut zeros = 0;
+    for ch in digits.chars().chain(Some('\0')) {
+        if ch == '0' {
+            zeros += 1;
+            continue;
+        }
+        while zeros > 0 {
+            let run = zeros.min(39);
+            match run {
+                1 => code.push('0'),
+                2 => code.push('w'),
+                3 => code.push('x'),
+                _ => {
+                    code.push('y');
+                    code.push(ZERO_RUN_DIGITS[run - 4] as char);
+                }
+            }
+            zeros -= run;
+        }
+        if ch != '\0' {
+            code.push(ch);
+        }
+    }
 }
 
-/// Parse a Grid from a block of structured text.
+/// Write the Grid as a block of structured text, which can be parsed back into the same Grid
+/// (shifted so that its top-left corner is at the origin).
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_string_with(&Alphabet::default()))
+    }
+}
+
+/// Parse a Grid from a block of structured text. Comments are skipped; parse a Pattern to keep
+/// them.
 impl FromStr for Grid {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let mut cells = Vec::new();
-
-        for (y, line) in s
-            .trim()
-            .lines()
-            .filter(|line| !line.starts_with(COMMENT_CHAR))
-            .enumerate()
-        {
-            for (x, ch) in line.chars().enumerate() {
-                match ch {
-                    // Living Points are added to the Grid.
-                    READ_CHAR_ALIVE => cells.push(Point(x as i64, y as i64)),
-                    // Dead Points are ignored.
-                    READ_CHAR_DEAD => (),
-                    // Skip the rest of the line after a comment char.
-                    COMMENT_CHAR => break,
-                    // Anything else is invalid.
-                    _ => bail!(ErrorKind::ParseGrid(format!("unknown character: '{}'", ch))),
+        Grid::parse_with(s, &Alphabet::default())
+    }
+}
+
+// Grids are serialized compactly, as the position of their top-left corner and their rows in
+// run-length encoded form, e.g. `{"origin": [2, -1], "rle": "bo$2bo$3o!"}`.
+#[derive(Serialize, Deserialize)]
+struct RleGrid {
+    origin: Point,
+    rle: String,
+}
+
+// The forms a Grid can be read from. Older versions wrote out every cell.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GridRepr {
+    Rle(RleGrid),
+    Cells { cells: Vec<Point> },
+}
+
+impl Serialize for Grid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        RleGrid {
+            origin: self.bounds().0,
+            rle: encode_rle(self),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Grid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+        match GridRepr::deserialize(deserializer)? {
+            GridRepr::Rle(RleGrid { origin, rle }) => {
+                decode_rle(&rle).and_then(|grid| match grid.checked_translated(origin) {
+                    Some(grid) => Ok(grid),
+                    None => bail!("origin {} moves the grid out of range", origin),
+                })
+            }
+            GridRepr::Cells { cells } => Ok(Grid::new(cells)),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+// Encode the rows of a Grid, from its top-left corner, in the run-length encoding used by RLE
+// pattern files: `b` is a dead cell, `o` a live cell, `$` ends a row and `!` ends the pattern.
+// Each can be preceded by a count, and dead cells at the end of a row are left out.
+fn encode_rle(grid: &Grid) -> String {
+    fn push_run(rle: &mut String, count: i64, tag: char) {
+        if count > 1 {
+            rle.push_str(&count.to_string());
+        }
+        if count > 0 {
+            rle.push(tag);
+        }
+    }
+
+    let mut rle = String::new();
+    if !grid.is_empty() {
+        let (Point(x0, y0), Point(x1, y1)) = grid.bounds();
+        let mut rows_ended = 0;
+        for y in y0..=y1 {
+            let mut run = (0, 'b');
+            for x in x0..=x1 {
+                let tag = if grid.is_alive(&Point(x, y)) {
+                    'o'
+                } else {
+                    'b'
                 };
+                if tag != run.1 {
+                    if tag == 'o' {
+                        push_run(&mut rle, rows_ended, '$');
+                        rows_ended = 0;
+                    }
+                    push_run(&mut rle, run.0, run.1);
+                    run = (0, tag);
+                }
+                run.0 += 1;
+            }
+            if run.1 == 'o' {
+                push_run(&mut rle, run.0, run.1);
             }
+            rows_ended += 1;
         }
+    }
+    rle.push('!');
+    rle
+}
 
-        Ok(Grid::new(cells))
+// Decode rows encoded with `encode_rle`, with the top-left corner at the origin. Fails if the
+// grid would have more than MAX_DECODED_CELLS live cells, or reach out of range.
+fn decode_rle(rle: &str) -> Result<Grid> {
+    let mut cells = Vec::new();
+    let (mut x, mut y) = (0i64, 0i64);
+    let mut count = String::new();
+    for ch in rle.chars().filter(|ch| !ch.is_whitespace()) {
+        if ch.is_ascii_digit() {
+            count.push(ch);
+            continue;
+        }
+        let n: i64 = if count.is_empty() {
+            1
+        } else {
+            count
+                .parse()
+                .chain_err(|| format!("invalid run length '{}'", count))?
+        };
+        count.clear();
+        let out_of_range = || format!("run of {} '{}' is out of range", n, ch);
+        match ch {
+            'b' => x = x.checked_add(n).ok_or_else(out_of_range)?,
+            'o' => {
+                if n > (MAX_DECODED_CELLS - cells.len()) as i64 {
+                    bail!("grid has more than {} live cells", MAX_DECODED_CELLS);
+                }
+                let end = x.checked_add(n).ok_or_else(out_of_range)?;
+                cells.extend((x..end).map(|x| Point(x, y)));
+                x = end;
+            }
+            '$' => {
+                x = 0;
+                y = y.checked_add(n).ok_or_else(out_of_range)?;
+            }
+            '!' => return Ok(Grid::new(cells)),
+            _ => bail!("invalid character in run-length encoded grid: '{}'", ch),
+        }
     }
+    bail!("run-length encoded grid is missing its closing '!'")
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use std::default::Default;
+    use ErrorKind;
 
     mod constructors {
         use super::*;
@@ -179,7 +700,8 @@ mod test {
             // 010
             let grid: Grid = vec![
                 " \t".to_string(), // Leading whitespace should be removed.
-                format!( // Everything after an inline comment should be ignored.
+                format!(
+                    // Everything after an inline comment should be ignored.
                     "{}{}{}{}",
                     READ_CHAR_ALIVE, READ_CHAR_ALIVE, COMMENT_CHAR, READ_CHAR_ALIVE
                 ),
@@ -198,6 +720,129 @@ mod test {
             );
             assert!(Grid::from_str("abc\ndef").is_err())
         }
+
+        #[test]
+        fn test_from_str_error_location() {
+            let location = |s: &str| match Grid::from_str(s).unwrap_err().kind() {
+                ErrorKind::ParseGrid(errors) => (errors[0].line, errors[0].column),
+                kind => panic!("unexpected error: {}", kind),
+            };
+            assert_eq!(location("x.o"), (1, 3));
+            assert_eq!(location("x.\n.xo"), (2, 3));
+            assert_eq!(location("# comment\nx.\n\n..o"), (4, 3));
+            assert_eq!(
+                location("\n\n  x.o"),
+                (3, 5),
+                "leading whitespace should be counted"
+            );
+        }
+
+        #[test]
+        fn test_display() {
+            let grid = Grid::new(vec![Point(-2, 1), Point(0, 1), Point(-1, 3)]);
+            assert_eq!(grid.to_string(), "x.x\n...\n.x.\n");
+            assert_eq!(
+                grid.to_string().parse::<Grid>().unwrap(),
+                Grid::new(vec![Point(0, 0), Point(2, 0), Point(1, 2)]),
+                "should round-trip, shifted to the origin"
+            );
+            assert_eq!(Grid::empty().to_string(), "");
+        }
+    }
+
+    mod clusters {
+        use super::*;
+
+        #[test]
+        fn test_clusters() {
+            let grid = Grid::new(vec![
+                Point(-5, -5),
+                Point(-4, -4),
+                Point(-2, -4),
+                Point(3, 1),
+                Point(3, 2),
+            ]);
+            let clusters = grid.clusters(1);
+            assert_eq!(
+                clusters,
+                vec![
+                    Cluster {
+                        bounds: (Point(-5, -5), Point(-4, -4)),
+                        cells: Grid::new(vec![Point(-5, -5), Point(-4, -4)]),
+                    },
+                    Cluster {
+                        bounds: (Point(-2, -4), Point(-2, -4)),
+                        cells: Grid::new(vec![Point(-2, -4)]),
+                    },
+                    Cluster {
+                        bounds: (Point(3, 1), Point(3, 2)),
+                        cells: Grid::new(vec![Point(3, 1), Point(3, 2)]),
+                    },
+                ]
+            );
+            assert_eq!(
+                grid.clusters(2).len(),
+                2,
+                "cells 2 apart should be in the same cluster"
+            );
+            assert!(Grid::empty().clusters(1).is_empty());
+        }
+    }
+
+    mod canonical {
+        use super::*;
+
+        fn grid(s: &str) -> Grid {
+            s.parse().unwrap()
+        }
+
+        #[test]
+        fn test_normalized() {
+            let glider = grid(".x.\n..x\nxxx");
+            let normalized = glider.normalized();
+            assert_eq!(normalized.to_string(), "xxx\nx..\n.x.\n");
+            for other in &["x..\nx.x\nxx.", ".xx\nx.x\n..x", "xxx\n..x\n.x."] {
+                assert_eq!(grid(other).normalized(), normalized, "{:?}", other);
+            }
+            let moved = Grid::new(glider.iter().map(|&p| p + Point(-7, 3)));
+            assert_eq!(moved.normalized(), normalized);
+            assert_ne!(
+                grid("x.x\n.xx\n.x.").normalized(),
+                normalized,
+                "a different phase is a different pattern"
+            );
+            assert_eq!(Grid::empty().normalized(), Grid::empty());
+        }
+
+        #[test]
+        fn test_content_hash() {
+            let hash = grid("xxx\n..x").content_hash();
+            assert_eq!(grid("x\nx\nxx").content_hash(), hash);
+            assert_eq!(grid("..x.\nxxx.").content_hash(), hash);
+            assert_ne!(grid("xxx\n.x.").content_hash(), hash);
+            assert_eq!(
+                Grid::empty().content_hash(),
+                0xcbf2_9ce4_8422_2325,
+                "the hash shouldn't change between versions"
+            );
+        }
+
+        #[test]
+        fn test_wechsler() {
+            assert_eq!(grid("xx\nxx").wechsler(), "33");
+            assert_eq!(grid("x\nx\nx").wechsler(), "7");
+            assert_eq!(grid(".x.\n..x\nxxx").wechsler(), "153");
+            assert_eq!(grid("xxx\nx..\n.x.").wechsler(), "153");
+            assert_eq!(grid("x\n.\n.\n.\n.\nx").wechsler(), "1z1");
+            assert_eq!(Grid::empty().wechsler(), "");
+        }
+
+        #[test]
+        fn test_push_zero_runs() {
+            let mut code = String::new();
+            push_zero_runs(&mut code, "10020003000040");
+            assert_eq!(code, "1w2x3y040");
+        }
     }
 
     mod cells {
@@ -250,6 +895,15 @@ mod test {
             assert!(!grid.is_empty());
         }
 
+        #[test]
+        fn test_len() {
+            assert_eq!(Grid::empty().len(), 0);
+            assert_eq!(
+                Grid::new(vec![Point(0, 0), Point(-3, 2), Point(0, 0)]).len(),
+                2
+            );
+        }
+
         #[test]
         fn test_is_alive() {
             let grid = Grid::new(vec![Point(-1, 4), Point(8, 8)]);
@@ -270,6 +924,192 @@ mod test {
         }
     }
 
+    mod combining {
+        use super::*;
+
+        fn a() -> Grid {
+            Grid::new(vec![Point(-2, -1), Point(0, 0), Point(3, -4)])
+        }
+
+        fn b() -> Grid {
+            Grid::new(vec![Point(0, 0), Point(-2, 1), Point(3, -4), Point(-5, -5)])
+        }
+
+        #[test]
+        fn test_set_operations() {
+            assert_eq!(
+                a().union(&b()),
+                Grid::new(vec![
+                    Point(-2, -1),
+                    Point(0, 0),
+                    Point(3, -4),
+                    Point(-2, 1),
+                    Point(-5, -5),
+                ])
+            );
+            assert_eq!(
+                a().intersection(&b()),
+                Grid::new(vec![Point(0, 0), Point(3, -4)])
+            );
+            assert_eq!(a().difference(&b()), Grid::new(vec![Point(-2, -1)]));
+            assert_eq!(
+                a().xor(&b()),
+                Grid::new(vec![Point(-2, -1), Point(-2, 1), Point(-5, -5)])
+            );
+            assert_eq!(a().union(&Grid::empty()), a());
+            assert!(a().intersection(&Grid::empty()).is_empty());
+            assert!(a().difference(&a()).is_empty());
+            assert!(a().xor(&a()).is_empty());
+        }
+
+        #[test]
+        fn test_stamp() {
+            // A 3x2 block of cells, with a hole in it.
+            let pattern = Grid::new(vec![Point(0, 0), Point(1, 0), Point(2, 0), Point(0, 1)]);
+            let grid = Grid::new(vec![Point(-4, -2), Point(-3, -1), Point(-6, -3)]);
+
+            let mut or = grid.clone();
+            or.stamp(&pattern, Point(-4, -2), StampMode::Or);
+            assert!(
+                or.is_alive(&Point(-3, -1)),
+                "should keep cells under the holes"
+            );
+            assert!(or.is_alive(&Point(-2, -2)));
+            assert_eq!(or.len(), 6);
+
+            let mut overwrite = grid.clone();
+            overwrite.stamp(&pattern, Point(-4, -2), StampMode::Overwrite);
+            assert!(
+                !overwrite.is_alive(&Point(-3, -1)),
+                "should clear the holes"
+            );
+            assert!(
+                overwrite.is_alive(&Point(-6, -3)),
+                "should keep cells outside"
+            );
+            assert_eq!(overwrite.len(), 5);
+
+            let mut xor = grid.clone();
+            xor.stamp(&pattern, Point(-4, -2), StampMode::Xor);
+            assert!(!xor.is_alive(&Point(-4, -2)), "should kill live cells");
+            assert!(xor.is_alive(&Point(-3, -1)));
+            assert_eq!(xor.len(), 5);
+            xor.stamp(&pattern, Point(-4, -2), StampMode::Xor);
+            assert_eq!(xor, grid, "stamping twice with xor should undo it");
+
+            let mut empty = grid.clone();
+            empty.stamp(&Grid::empty(), Point(-4, -2), StampMode::Overwrite);
+            assert_eq!(empty, grid);
+        }
+    }
+
+    mod serialization {
+        use super::*;
+        use serde_json;
+
+        #[test]
+        fn test_serialize() {
+            let glider = Grid::new(vec![
+                Point(3, -1),
+                Point(4, 0),
+                Point(2, 1),
+                Point(3, 1),
+                Point(4, 1),
+            ]);
+            let json = serde_json::to_string(&glider).unwrap();
+            assert_eq!(json, r#"{"origin":[2,-1],"rle":"bo$2bo$3o!"}"#);
+            assert_eq!(serde_json::from_str::<Grid>(&json).unwrap(), glider);
+
+            let gaps = Grid::new(vec![Point(0, 0), Point(5, 0), Point(1, 4), Point(2, 4)]);
+            assert_eq!(encode_rle(&gaps), "o4bo4$b2o!");
+            assert_eq!(decode_rle(&encode_rle(&gaps)).unwrap(), gaps);
+            assert_eq!(encode_rle(&Grid::empty()), "!");
+            assert_eq!(decode_rle("!").unwrap(), Grid::empty());
+            for rle in &[
+                "9223372036854775807bo!",
+                "9223372036854775807$o$o!",
+                "999999999999o!",
+                "99999999999999999999o!",
+            ] {
+                assert!(decode_rle(rle).is_err(), "{}", rle);
+            }
+            let far = r#"{"origin": [9223372036854775807, 0], "rle": "bo!"}"#;
+            assert!(serde_json::from_str::<Grid>(far).is_err());
+
+            let grid = Grid::random(64, 64, 0.5, 1).unwrap();
+            let json = serde_json::to_string(&grid).unwrap();
+            assert_eq!(serde_json::from_str::<Grid>(&json).unwrap(), grid);
+
+            assert!(Grid::random(0, 64, 0.5, 1).is_err());
+            assert!(Grid::random(64, 64, 1.5, 1).is_err());
+        }
+
+        #[test]
+        fn test_deserialize_cells() {
+            let grid: Grid = serde_json::from_str(r#"{"cells": [[0, 0], [2, -1]]}"#).unwrap();
+            assert_eq!(grid, Grid::new(vec![Point(0, 0), Point(2, -1)]));
+            for json in &[
+                r#"{"origin": [0, 0], "rle": "2o"}"#,
+                r#"{"origin": [0, 0], "rle": "3x!"}"#,
+                r#"{"rle": "o!"}"#,
+            ] {
+                assert!(serde_json::from_str::<Grid>(json).is_err(), "{}", json);
+            }
+        }
+    }
+
+    mod transforms {
+        use super::*;
+
+        // An L-tromino away from the origin, so that the corner has to be kept.
+        fn grid() -> Grid {
+            Grid::new(vec![Point(-3, 2), Point(-3, 3), Point(-2, 3)])
+        }
+
+        #[test]
+        fn test_translate() {
+            let mut grid = grid();
+            grid.translate(Point(3, -2));
+            assert_eq!(grid, Grid::new(vec![Point(0, 0), Point(0, 1), Point(1, 1)]));
+            assert_eq!(grid.translated(Point(-3, 2)), self::grid());
+        }
+
+        #[test]
+        fn test_rotate() {
+            let rotated = grid().rotated(Rotation::R90);
+            assert_eq!(rotated.to_string(), "xx\nx.\n");
+            assert_eq!(rotated.bounds().0, Point(-3, 2), "should keep the corner");
+            assert_eq!(grid().rotated(Rotation::R180).to_string(), "xx\n.x\n");
+            assert_eq!(grid().rotated(Rotation::R270).to_string(), ".x\nxx\n");
+
+            let mut grid = grid();
+            for _ in 0..4 {
+                grid.rotate(Rotation::R90);
+            }
+            assert_eq!(grid, self::grid());
+        }
+
+        #[test]
+        fn test_flip_and_transpose() {
+            assert_eq!(grid().flipped(Flip::Horizontal).to_string(), ".x\nxx\n");
+            assert_eq!(grid().flipped(Flip::Vertical).to_string(), "xx\nx.\n");
+            let glider: Grid = ".x.\n..x\nxxx".parse().unwrap();
+            assert_eq!(glider.transposed().to_string(), "..x\nx.x\n.xx\n");
+
+            let mut grid = grid();
+            grid.transpose();
+            grid.flip(Flip::Vertical);
+            assert_eq!(
+                grid,
+                self::grid().rotated(Rotation::R270),
+                "transposing then flipping should rotate"
+            );
+            let mut empty = Grid::empty();
+            empty.transpose();
+            assert!(empty.is_empty());
+        }
+    }
+
     mod geometry {
         use super::*;
 
@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+
+use grid::Grid;
+use {ErrorKind, Result};
+
+/// Default number of generations between keyframes.
+pub const DEFAULT_INTERVAL: u64 = 16;
+/// Default maximum number of keyframes to keep.
+pub const DEFAULT_CAPACITY: usize = 64;
+/// Default maximum number of live cells to keep across all keyframes.
+pub const DEFAULT_MAX_CELLS: usize = 1 << 20;
+
+/// History keeps a bounded record of past generations of a Game.
+///
+/// Rather than storing every generation, a copy of the grid (a keyframe) is kept every `interval`
+/// generations, and at most `capacity` keyframes holding at most `max_cells` live cells between
+/// them are kept at a time. Any generation after the oldest keyframe can be recovered by
+/// re-simulating forward from the keyframe before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct History {
+    interval: u64,
+    capacity: usize,
+    #[serde(default = "default_max_cells")]
+    max_cells: usize,
+    keyframes: VecDeque<(u64, Grid)>,
+}
+
+fn default_max_cells() -> usize {
+    DEFAULT_MAX_CELLS
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(DEFAULT_INTERVAL, DEFAULT_CAPACITY)
+    }
+}
+
+impl History {
+    /// Create a new History. `interval` and `capacity` must be greater than 0.
+    pub fn new(interval: u64, capacity: usize) -> Self {
+        assert!(interval > 0, "history interval must be greater than 0");
+        assert!(capacity > 0, "history capacity must be greater than 0");
+        History {
+            interval,
+            capacity,
+            max_cells: DEFAULT_MAX_CELLS,
+            keyframes: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Keep at most `max_cells` live cells across all keyframes, instead of the default.
+    ///
+    /// Grids larger than this are never recorded, so generations after them can only be
+    /// recovered from older keyframes.
+    pub fn with_max_cells(mut self, max_cells: usize) -> Self {
+        self.max_cells = max_cells;
+        self
+    }
+
+    /// Create a History that never records anything, for Games that don't need to go back.
+    pub fn disabled() -> Self {
+        History {
+            interval: 1,
+            capacity: 0,
+            max_cells: 0,
+            keyframes: VecDeque::new(),
+        }
+    }
+
+    /// Check that a History read from a snapshot could have been made by `new` or `disabled`: the
+    /// interval is greater than 0, and the keyframes are in order and within the capacity and the
+    /// cell limit.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| ErrorKind::InvalidConfig("history", reason.to_string());
+        if self.interval == 0 {
+            bail!(invalid("interval must be greater than 0"));
+        }
+        if self.keyframes.len() > self.capacity {
+            bail!(invalid("has more keyframes than its capacity"));
+        }
+        if self.cells() > self.max_cells {
+            bail!(invalid("has more cells than its limit"));
+        }
+        let generations: Vec<u64> = self.keyframes.iter().map(|&(g, _)| g).collect();
+        if generations.windows(2).any(|pair| pair[0] >= pair[1]) {
+            bail!(invalid("keyframes are out of order"));
+        }
+        Ok(())
+    }
+
+    /// Record the grid at the given generation, if it has been at least `interval` generations
+    /// since the last keyframe or if there are no keyframes yet. The oldest keyframes are dropped
+    /// to stay within the capacity and the cell limit.
+    pub fn record(&mut self, generation: u64, grid: &Grid) {
+        if self.capacity == 0 || grid.len() > self.max_cells {
+            return;
+        }
+        match self.keyframes.back() {
+            Some(&(last, _)) if generation < last + self.interval => return,
+            _ => (),
+        }
+        if self.keyframes.len() == self.capacity {
+            self.keyframes.pop_front();
+        }
+        while self.cells() + grid.len() > self.max_cells {
+            self.keyframes.pop_front();
+        }
+        self.keyframes.push_back((generation, grid.clone()));
+    }
+
+    /// Return the latest keyframe at or before the given generation.
+    pub fn keyframe(&self, generation: u64) -> Option<(u64, &Grid)> {
+        self.keyframes
+            .iter()
+            .rev()
+            .find(|&&(g, _)| g <= generation)
+            .map(|&(g, ref grid)| (g, grid))
+    }
+
+    /// Return the oldest generation that can still be recovered, if any.
+    pub fn earliest(&self) -> Option<u64> {
+        self.keyframes.front().map(|&(g, _)| g)
+    }
+
+    /// Forget all keyframes after the given generation.
+    pub fn truncate(&mut self, generation: u64) {
+        while let Some(&(g, _)) = self.keyframes.back() {
+            if g <= generation {
+                break;
+            }
+            self.keyframes.pop_back();
+        }
+    }
+
+    /// Return the number of live cells across all keyframes.
+    fn cells(&self) -> usize {
+        self.keyframes.iter().map(|keyframe| keyframe.1.len()).sum()
+    }
+
+    /// Forget all keyframes.
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use point::Point;
+
+    fn grid(n: i64) -> Grid {
+        Grid::new(vec![Point(n, 0)])
+    }
+
+    #[test]
+    fn test_record() {
+        let mut history = History::new(2, 3);
+        history.record(1, &grid(1));
+        assert_eq!(
+            history.earliest(),
+            Some(1),
+            "should always record the first keyframe"
+        );
+        for g in 2..10 {
+            history.record(g, &grid(g as i64));
+        }
+        assert_eq!(
+            history.earliest(),
+            Some(5),
+            "should drop the oldest keyframes"
+        );
+        assert_eq!(history.keyframe(8), Some((7, &grid(7))));
+        assert_eq!(history.keyframe(9), Some((9, &grid(9))));
+        assert_eq!(history.keyframe(4), None);
+
+        history.truncate(6);
+        assert_eq!(history.keyframe(9), Some((5, &grid(5))));
+
+        let mut history = History::disabled();
+        history.record(0, &grid(0));
+        assert_eq!(history.earliest(), None);
+    }
+
+    #[test]
+    fn test_record_max_cells() {
+        let mut history = History::new(1, 8).with_max_cells(3);
+        let big = Grid::new((0..2).map(|x| Point(x, 0)));
+        history.record(0, &grid(0));
+        history.record(1, &grid(1));
+        history.record(2, &big);
+        assert_eq!(
+            history.earliest(),
+            Some(1),
+            "should drop the oldest keyframes to make room"
+        );
+        assert_eq!(history.keyframe(2), Some((2, &big)));
+
+        let huge = Grid::new((0..4).map(|x| Point(x, 0)));
+        history.record(3, &huge);
+        assert_eq!(
+            history.keyframe(3),
+            Some((2, &big)),
+            "should skip grids over the limit"
+        );
+        assert_eq!(history.earliest(), Some(1));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut history = History::new(2, 3);
+        history.record(1, &grid(1));
+        history.record(3, &grid(3));
+        assert!(history.validate().is_ok());
+        assert!(History::disabled().validate().is_ok());
+
+        let mut reversed = history.clone();
+        reversed.keyframes.swap(0, 1);
+        let mut full = history.clone();
+        full.capacity = 1;
+        let crowded = history.clone().with_max_cells(1);
+        for history in &[
+            History {
+                interval: 0,
+                ..History::default()
+            },
+            reversed,
+            full,
+            crowded,
+        ] {
+            assert!(history.validate().is_err(), "{:?}", history);
+        }
+    }
+}
@@ -0,0 +1,642 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use gif;
+use png;
+
+use game::Game;
+use grid::{Grid, Point};
+use soup::parse_size;
+use {Error, ErrorKind, Result};
+
+/// The largest image that will be drawn, in pixels.
+pub const MAX_IMAGE_PIXELS: u64 = 1 << 24;
+
+/// An RGB color, written as `#rrggbb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl FromStr for Color {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid =
+            || ErrorKind::InvalidConfig("color", format!("expected #rrggbb, got '{}'", s));
+        let hex = s.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            bail!(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Color(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// The part of a Grid to draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// The Game's viewport. A Grid on its own has no viewport, so this is the same as `Pattern`.
+    Viewport,
+    /// The smallest rectangle that holds every live cell.
+    Pattern,
+    /// The rectangle between two corners, inclusive.
+    Rect(Point, Point),
+}
+
+impl Region {
+    /// Return the lowest and highest X and Y coordinates of the Region.
+    pub fn bounds(&self, grid: &Grid, viewport: Option<(Point, Point)>) -> (Point, Point) {
+        match (*self, viewport) {
+            (Region::Viewport, Some(viewport)) => viewport,
+            (Region::Viewport, None) | (Region::Pattern, _) => grid.bounds(),
+            (Region::Rect(min, max), _) => (min, max),
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = Error;
+
+    /// Parse `viewport`, `pattern`, or a rectangle given as `WxH@X,Y`, e.g. `32x16@-8,0`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            ErrorKind::InvalidConfig(
+                "region",
+                format!("expected viewport, pattern or WxH@X,Y, got '{}'", s),
+            )
+        };
+        match s.trim() {
+            "viewport" => Ok(Region::Viewport),
+            "pattern" => Ok(Region::Pattern),
+            rect => {
+                let mut parts = rect.splitn(2, '@');
+                let (width, height) = parts.next().and_then(parse_size).ok_or_else(invalid)?;
+                let mut corner = parts.next().ok_or_else(invalid)?.splitn(2, ',');
+                let mut coord = || {
+                    corner
+                        .next()
+                        .and_then(|n| n.trim().parse::<i64>().ok())
+                        .ok_or_else(invalid)
+                };
+                let (x, y) = (coord()?, coord()?);
+                if width == 0 || height == 0 {
+                    bail!(invalid());
+                }
+                // The far corner has to fit in an i64 too.
+                let far = |start: i64, len: u64| {
+                    i64::try_from(len - 1)
+                        .ok()
+                        .and_then(|len| start.checked_add(len))
+                };
+                match (far(x, width), far(y, height)) {
+                    (Some(x1), Some(y1)) => Ok(Region::Rect(Point(x, y), Point(x1, y1))),
+                    _ => bail!(ErrorKind::InvalidConfig(
+                        "region",
+                        format!("'{}' is out of range", s)
+                    )),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Region::Viewport => write!(f, "viewport"),
+            Region::Pattern => write!(f, "pattern"),
+            Region::Rect(Point(x0, y0), Point(x1, y1)) => {
+                write!(f, "{}x{}@{},{}", span(x0, x1), span(y0, y1), x0, y0)
+            }
+        }
+    }
+}
+
+/// Options for drawing a Grid as an Image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// Width and height of each cell, in pixels.
+    pub cell_size: u32,
+    /// Whether to draw 1 pixel lines between cells.
+    pub gridlines: bool,
+    pub alive: Color,
+    pub dead: Color,
+    pub gridline: Color,
+    pub region: Region,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            cell_size: 8,
+            gridlines: false,
+            alive: Color(0x00, 0x00, 0x00),
+            dead: Color(0xff, 0xff, 0xff),
+            gridline: Color(0xc0, 0xc0, 0xc0),
+            region: Region::Pattern,
+        }
+    }
+}
+
+impl ImageOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.cell_size == 0 {
+            bail!(ErrorKind::InvalidConfig(
+                "cell_size",
+                "must be greater than 0".to_string()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Return the width and height in pixels of an image of the cells between the given bounds.
+    pub fn image_size(&self, (Point(x0, y0), Point(x1, y1)): (Point, Point)) -> (u64, u64) {
+        let line = if self.gridlines { 1 } else { 0 };
+        let pitch = u64::from(self.cell_size) + line;
+        let (cols, rows) = (span(x0, x1), span(y0, y1));
+        (
+            cols.saturating_mul(pitch).saturating_add(line),
+            rows.saturating_mul(pitch).saturating_add(line),
+        )
+    }
+}
+
+// Return the number of cells from `min` to `max` inclusive, up to u64::MAX.
+fn span(min: i64, max: i64) -> u64 {
+    if max < min {
+        0
+    } else {
+        (max.wrapping_sub(min) as u64).saturating_add(1)
+    }
+}
+
+/// An RGB image of part of a Grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Draw the cells of `grid` between the given bounds.
+    pub fn draw(grid: &Grid, bounds: (Point, Point), opts: &ImageOptions) -> Result<Image> {
+        opts.validate()?;
+        let (width, height) = opts.image_size(bounds);
+        check_size(width, height)?;
+        let Color(r, g, b) = if opts.gridlines {
+            opts.gridline
+        } else {
+            opts.dead
+        };
+        let mut image = Image {
+            width: width as u32,
+            height: height as u32,
+            pixels: [r, g, b].repeat((width * height) as usize),
+        };
+        let (Point(x0, y0), Point(x1, y1)) = bounds;
+        let line = if opts.gridlines { 1 } else { 0 };
+        let pitch = opts.cell_size + line;
+        let size = opts.cell_size;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let color = if grid.is_alive(&Point(x, y)) {
+                    opts.alive
+                } else {
+                    opts.dead
+                };
+                let left = (x - x0) as u32 * pitch + line;
+                let top = (y - y0) as u32 * pitch + line;
+                image.fill(left, top, size, size, color);
+            }
+        }
+        Ok(image)
+    }
+
+    /// Return the color of the pixel at `(x, y)`.
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let i = ((y * self.width + x) * 3) as usize;
+        Color(self.pixels[i], self.pixels[i + 1], self.pixels[i + 2])
+    }
+
+    /// Return the pixels as rows of RGB bytes, from the top left.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Encode the Image as a PNG.
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Save the Image to a PNG file.
+    pub fn save_png(&self, path: &Path) -> Result<()> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+
+    // Return the index of each pixel's color in the palette.
+    fn indices(&self, palette: &[Color]) -> Vec<u8> {
+        self.pixels
+            .chunks(3)
+            .map(|pixel| {
+                let color = Color(pixel[0], pixel[1], pixel[2]);
+                palette.iter().position(|&c| c == color).unwrap_or(0) as u8
+            })
+            .collect()
+    }
+
+    fn fill(&mut self, left: u32, top: u32, width: u32, height: u32, Color(r, g, b): Color) {
+        for y in top..top + height {
+            let start = ((y * self.width + left) * 3) as usize;
+            for pixel in self.pixels[start..start + width as usize * 3].chunks_mut(3) {
+                pixel.copy_from_slice(&[r, g, b]);
+            }
+        }
+    }
+}
+
+// Check that an image of the given size is within MAX_IMAGE_PIXELS.
+fn check_size(width: u64, height: u64) -> Result<()> {
+    if width.saturating_mul(height) > MAX_IMAGE_PIXELS {
+        bail!(ErrorKind::InvalidConfig(
+            "region",
+            format!(
+                "the image would be {}x{} pixels, but the limit is {} pixels",
+                width, height, MAX_IMAGE_PIXELS
+            )
+        ));
+    }
+    Ok(())
+}
+
+/// Options for recording a run of a Game as an Animation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationOptions {
+    /// How to draw each frame. `Region::Pattern` is the smallest rectangle that holds the
+    /// pattern in every frame, so it grows to fit the pattern as it spreads.
+    pub image: ImageOptions,
+    /// The generation to start from, if not the Game's current one.
+    pub start: Option<u64>,
+    /// The largest number of frames to record. Recording stops early if the Game is over.
+    pub frames: u64,
+    /// The number of generations between frames.
+    pub step: u64,
+    /// How long each frame is shown for.
+    pub frame_delay: Duration,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        AnimationOptions {
+            image: ImageOptions::default(),
+            start: None,
+            frames: 100,
+            step: 1,
+            frame_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl AnimationOptions {
+    pub fn validate(&self) -> Result<()> {
+        self.image.validate()?;
+        for &(field, value) in &[("frames", self.frames), ("frame_step", self.step)] {
+            if value == 0 {
+                bail!(ErrorKind::InvalidConfig(
+                    field,
+                    "must be greater than 0".to_string()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the last generation that would be recorded.
+    pub fn end(&self, start: u64) -> u64 {
+        start.saturating_add(self.frames.saturating_sub(1).saturating_mul(self.step))
+    }
+}
+
+/// An Animation is a recorded run of a Game, which can be encoded as an animated GIF.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    frames: Vec<(Grid, (Point, Point))>,
+    options: AnimationOptions,
+}
+
+impl Animation {
+    /// Run the Game without delay, recording a frame every `options.step` generations.
+    pub fn record(game: &mut Game, options: &AnimationOptions) -> Result<Animation> {
+        options.validate()?;
+        if let Some(start) = options.start {
+            game.seek(start)?;
+        }
+        let region = options.image.region;
+        let frame = |game: &Game| {
+            let bounds = region.bounds(game.grid(), Some(game.viewport()));
+            (game.grid().clone(), bounds)
+        };
+        let mut frames = vec![frame(game)];
+        let mut turns = game.iter();
+        'recording: while (frames.len() as u64) < options.frames {
+            for _ in 0..options.step {
+                if turns.next().is_none() {
+                    break 'recording;
+                }
+            }
+            frames.push(frame(turns.game()));
+        }
+
+        if region == Region::Pattern {
+            let bounds = frames
+                .iter()
+                .fold(Grid::empty(), |all, (grid, _)| all.union(grid))
+                .bounds();
+            for frame in &mut frames {
+                frame.1 = bounds;
+            }
+        }
+        let animation = Animation {
+            frames,
+            options: options.clone(),
+        };
+        let (width, height) = animation.size();
+        check_size(width, height)?;
+        if width > u64::from(u16::MAX) || height > u64::from(u16::MAX) {
+            bail!(ErrorKind::InvalidConfig(
+                "region",
+                format!("a GIF can't be {}x{} pixels", width, height)
+            ));
+        }
+        Ok(animation)
+    }
+
+    /// Return the number of frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Return whether there are no frames. This is never true of a recorded Animation.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Return the width and height of each frame, in pixels.
+    pub fn size(&self) -> (u64, u64) {
+        self.options.image.image_size(self.frames[0].1)
+    }
+
+    /// Encode the Animation as a GIF that loops forever.
+    pub fn write_gif<W: Write>(&self, writer: W) -> Result<()> {
+        let opts = &self.options.image;
+        let palette = [opts.dead, opts.alive, opts.gridline];
+        let palette_bytes: Vec<u8> = palette.iter().flat_map(|c| vec![c.0, c.1, c.2]).collect();
+        let (width, height) = self.size();
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &palette_bytes)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        let millis = self.options.frame_delay.as_secs() * 1000
+            + u64::from(self.options.frame_delay.subsec_millis());
+        let delay = (millis / 10).min(u64::from(u16::MAX)) as u16;
+        for (grid, bounds) in &self.frames {
+            let image = Image::draw(grid, *bounds, opts)?;
+            let frame = gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                delay,
+                buffer: Cow::Owned(image.indices(&palette)),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Save the Animation to a GIF file.
+    pub fn save_gif(&self, path: &Path) -> Result<()> {
+        self.write_gif(BufWriter::new(File::create(path)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config::Settings;
+    use game::View;
+
+    const BLACK: Color = Color(0, 0, 0);
+    const WHITE: Color = Color(0xff, 0xff, 0xff);
+    const GREY: Color = Color(0xc0, 0xc0, 0xc0);
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!("#ff8000".parse::<Color>().unwrap(), Color(0xff, 0x80, 0));
+        assert_eq!("0a0B0c".parse::<Color>().unwrap(), Color(0x0a, 0x0b, 0x0c));
+        assert_eq!(Color(0x0a, 0x0b, 0x0c).to_string(), "#0a0b0c");
+        for s in &["", "#fff", "#gg0000", "#ff00001", "#ÿÿÿ"] {
+            assert!(s.parse::<Color>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!("viewport".parse::<Region>().unwrap(), Region::Viewport);
+        assert_eq!("pattern".parse::<Region>().unwrap(), Region::Pattern);
+        let rect = "32x16@-8,4".parse::<Region>().unwrap();
+        assert_eq!(rect, Region::Rect(Point(-8, 4), Point(23, 19)));
+        assert_eq!(rect.to_string(), "32x16@-8,4");
+        for s in &["", "32x16", "32x16@1", "0x16@1,1", "32x16@a,1"] {
+            assert!(s.parse::<Region>().is_err(), "{}", s);
+        }
+
+        // The far corner has to fit in an i64.
+        let edge = format!("2x1@{},0", i64::MAX - 1).parse::<Region>().unwrap();
+        assert_eq!(
+            edge,
+            Region::Rect(Point(i64::MAX - 1, 0), Point(i64::MAX, 0))
+        );
+        for s in &[
+            format!("2x1@{},0", i64::MAX),
+            format!("{}x1@0,0", u64::MAX),
+            format!("1x{}@0,1", 1u64 << 63),
+        ] {
+            assert!(s.parse::<Region>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_draw() {
+        let grid: Grid = "x.\n.x".parse().unwrap();
+        let opts = ImageOptions {
+            cell_size: 2,
+            ..ImageOptions::default()
+        };
+        let image = grid.to_image(&opts).unwrap();
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(image.pixel(1, 1), BLACK);
+        assert_eq!(image.pixel(2, 1), WHITE);
+        assert_eq!(image.pixel(3, 3), BLACK);
+
+        let image = grid
+            .to_image(&ImageOptions {
+                gridlines: true,
+                region: Region::Rect(Point(1, 1), Point(2, 1)),
+                ..opts
+            })
+            .unwrap();
+        assert_eq!((image.width, image.height), (7, 4));
+        let row: Vec<Color> = (0..7).map(|x| image.pixel(x, 1)).collect();
+        assert_eq!(row, vec![GREY, BLACK, BLACK, GREY, WHITE, WHITE, GREY]);
+        assert_eq!(image.pixel(1, 0), GREY);
+        assert_eq!(image.pixel(1, 3), GREY);
+    }
+
+    #[test]
+    fn test_draw_invalid() {
+        let grid: Grid = "x".parse().unwrap();
+        let too_large = ImageOptions {
+            region: Region::Rect(Point(0, 0), Point(1 << 20, 0)),
+            ..ImageOptions::default()
+        };
+        let widest = ImageOptions {
+            gridlines: true,
+            region: Region::Rect(Point(i64::MIN, 0), Point(i64::MAX, 0)),
+            ..ImageOptions::default()
+        };
+        assert_eq!(
+            widest.image_size(widest.region.bounds(&grid, None)).0,
+            u64::MAX
+        );
+        for opts in &[
+            too_large,
+            widest,
+            ImageOptions {
+                cell_size: 0,
+                ..ImageOptions::default()
+            },
+        ] {
+            assert!(grid.to_image(opts).is_err(), "{:?}", opts);
+        }
+    }
+
+    #[test]
+    fn test_write_png() {
+        let grid: Grid = ".x.\n..x\nxxx".parse().unwrap();
+        let image = grid.to_image(&ImageOptions::default()).unwrap();
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+
+        let mut reader = png::Decoder::new(&png[..]).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (24, 24));
+        assert_eq!(&pixels[..info.buffer_size()], image.pixels());
+    }
+
+    fn mk_glider() -> Game {
+        let grid: Grid = ".x.\n..x\nxxx".parse().unwrap();
+        let settings = Settings {
+            view: View::Fixed,
+            ..Settings::default()
+        };
+        Game::new(grid, settings, (Some(10), Some(10)))
+    }
+
+    #[test]
+    fn test_record() {
+        let mut game = mk_glider();
+        let opts = AnimationOptions {
+            start: Some(2),
+            frames: 3,
+            step: 4,
+            ..AnimationOptions::default()
+        };
+        let animation = Animation::record(&mut game, &opts).unwrap();
+        assert_eq!(game.generation(), 10);
+        assert_eq!(animation.len(), 3);
+        // The pattern region grows to fit the glider as it moves 2 cells down and right.
+        assert_eq!(animation.size(), (5 * 8, 5 * 8));
+
+        let mut game = mk_glider();
+        let opts = AnimationOptions {
+            image: ImageOptions {
+                region: Region::Viewport,
+                ..ImageOptions::default()
+            },
+            ..opts
+        };
+        let animation = Animation::record(&mut game, &opts).unwrap();
+        assert_eq!(animation.size(), (10 * 8, 10 * 8));
+    }
+
+    #[test]
+    fn test_record_stops_when_game_is_over() {
+        let mut game = Game::new("x".parse().unwrap(), Settings::default(), (None, None));
+        let animation = Animation::record(&mut game, &AnimationOptions::default()).unwrap();
+        assert_eq!(animation.len(), 2);
+    }
+
+    #[test]
+    fn test_record_invalid() {
+        for opts in &[
+            AnimationOptions {
+                frames: 0,
+                ..AnimationOptions::default()
+            },
+            AnimationOptions {
+                step: 0,
+                ..AnimationOptions::default()
+            },
+        ] {
+            assert!(Animation::record(&mut mk_glider(), opts).is_err());
+        }
+    }
+
+    #[test]
+    fn test_write_gif() {
+        let opts = AnimationOptions {
+            frames: 4,
+            frame_delay: Duration::from_millis(250),
+            image: ImageOptions {
+                cell_size: 1,
+                ..ImageOptions::default()
+            },
+            ..AnimationOptions::default()
+        };
+        let animation = Animation::record(&mut mk_glider(), &opts).unwrap();
+        let mut gif = Vec::new();
+        animation.write_gif(&mut gif).unwrap();
+
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = decoder.read_info(&gif[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (4, 4));
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 25);
+            frames.push(frame.buffer.to_vec());
+        }
+        assert_eq!(frames.len(), 4);
+        // The first frame is the glider in the top left corner, alive cells being palette index 1.
+        assert_eq!(
+            frames[0],
+            vec![0, 1, 0, 0, 0, 0, 1, 0, 1, 1, 1, 0, 0, 0, 0, 0]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use game::Game;
+use {Error, ErrorKind, Result};
+
+/// The commands that can be typed in interactive mode.
+pub const HELP: &str = "[Enter] next, b [N] back N generations, q quit";
+
+/// A command typed in interactive mode, one per line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// An empty line or `n`: go on to the next generation.
+    Next,
+    /// `b`, or `b N`: go back one or N generations.
+    Back(u64),
+    /// `q`: stop.
+    Quit,
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut words = s.split_whitespace();
+        let key = match (words.next(), words.next()) {
+            (None, _) | (Some("n"), None) => Key::Next,
+            (Some("b"), None) => Key::Back(1),
+            (Some("b"), Some(n)) => Key::Back(
+                n.parse()
+                    .map_err(|_| ErrorKind::ParseArg("b", "a number of generations"))?,
+            ),
+            (Some("q"), None) => Key::Quit,
+            _ => bail!("unknown command '{}'", s.trim()),
+        };
+        if words.next().is_some() {
+            bail!("unknown command '{}'", s.trim());
+        }
+        Ok(key)
+    }
+}
+
+/// Step through the Game by hand, reading a Key from each line of `input` and writing the Game
+/// to `output` after each one. Stops at the end of the input, on `q`, or when the Game is over.
+pub fn run_interactive<R, W>(game: &mut Game, input: R, mut output: W) -> Result<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    write!(
+        output,
+        "{}generation {} | {} > ",
+        game.draw(),
+        game.generation(),
+        HELP
+    )?;
+    output.flush()?;
+    for line in input.lines() {
+        match line?.parse() {
+            Ok(Key::Next) => {
+                if game.is_over() {
+                    break;
+                }
+                game.tick();
+            }
+            Ok(Key::Back(n)) => game.step_back(n)?,
+            Ok(Key::Quit) => break,
+            Err(err) => {
+                write!(output, "{} > ", err)?;
+                output.flush()?;
+                continue;
+            }
+        }
+        write!(
+            output,
+            "\n{}generation {} > ",
+            game.draw(),
+            game.generation()
+        )?;
+        output.flush()?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config::Settings;
+    use grid::Grid;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!("".parse::<Key>().unwrap(), Key::Next);
+        assert_eq!(" n ".parse::<Key>().unwrap(), Key::Next);
+        assert_eq!("b".parse::<Key>().unwrap(), Key::Back(1));
+        assert_eq!("b 12".parse::<Key>().unwrap(), Key::Back(12));
+        assert_eq!("q".parse::<Key>().unwrap(), Key::Quit);
+        for s in &["x", "b -1", "b 1 2", "q now"] {
+            assert!(s.parse::<Key>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_run_interactive() {
+        let mk_game = || {
+            let grid: Grid = ".x.\n..x\nxxx".parse().unwrap();
+            Game::new(grid, Settings::default(), (Some(6), Some(6)))
+        };
+        let mut expected = mk_game();
+        expected.tick();
+        let frame = expected.draw();
+
+        let mut game = mk_game();
+        let input = Cursor::new("\n\n\nwhat\nb 2\nq\n\n");
+        let mut output = Vec::new();
+        run_interactive(&mut game, input, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // Three steps forward, then two back, and nothing after `q`.
+        assert_eq!(game.generation(), 1);
+        assert_eq!(game.grid(), expected.grid());
+        let generations: Vec<&str> = output
+            .split("generation ")
+            .skip(1)
+            .map(|s| s.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(generations, vec!["0", "1", "2", "3", "1"]);
+        assert!(output.contains("unknown command 'what'"));
+        assert!(output.ends_with(&format!("\n{}generation 1 > \n", frame)));
+    }
+}
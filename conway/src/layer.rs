@@ -0,0 +1,597 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::ArgMatches;
+use serde_json;
+use toml;
+
+use alphabet::Alphabet;
+use catalog::SAMPLES;
+use config::{GameConfig, Settings, SettingsUpdate};
+use game::{Output, OutputOptions, View};
+use grid::Point;
+use scene::Placement;
+use soup::SoupSpec;
+use transform::{Flip, Rotation, Transform};
+use {ErrorKind, Result, ResultExt};
+
+/// Environment variable that gives the path of the config file.
+pub const CONFIG_VAR: &str = "CONWAY_CONFIG";
+/// Environment variable that selects a profile from the config file.
+pub const PROFILE_VAR: &str = "CONWAY_PROFILE";
+
+/// One layer of configuration for a Game, from the config file, the environment or the command
+/// line. Fields that are `None` are left to the layers below.
+///
+/// In a config file the fields are named after the command line options, e.g.
+///
+/// ```toml
+/// delay = 100
+/// live_char = "o"
+/// sample = "glider"
+///
+/// [profiles.soup]
+/// soup = "32x32:0.5:7:C2"
+///
+/// [[profiles.soup.place]]
+/// source = { sample = "glider" }
+/// offset = [40, 0]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigLayer {
+    /// Delay between ticks, in milliseconds.
+    pub delay: Option<u64>,
+    pub view: Option<View>,
+    pub live_char: Option<char>,
+    pub dead_char: Option<char>,
+    /// The characters read as live and dead cells in `pattern` or `file`. If `#` is one of them,
+    /// the pattern can't have comments.
+    pub read_alive: Option<String>,
+    pub read_dead: Option<String>,
+    pub output: Option<Output>,
+    pub stats: Option<bool>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+
+    /// The pattern, given directly. Only one of `pattern`, `file`, `sample` and `soup` can be
+    /// given in a layer, and giving one replaces any source given in the layers below.
+    pub pattern: Option<String>,
+    pub file: Option<PathBuf>,
+    pub sample: Option<String>,
+    pub soup: Option<String>,
+
+    pub transpose: Option<bool>,
+    pub flip: Option<Flip>,
+    pub rotate: Option<Rotation>,
+    pub translate: Option<Point>,
+    pub place: Option<Vec<Placement>>,
+
+    /// Named layers that can be applied on top of this one. Only used in config files.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ConfigLayer>,
+}
+
+impl ConfigLayer {
+    /// Read a ConfigLayer from a TOML or JSON file, depending on its extension.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = read_to_string(path)
+            .chain_err(|| format!("failed to read config file {}", path.display()))?;
+        let layer: ConfigLayer = if path.extension() == Some(OsStr::new("json")) {
+            serde_json::from_str(&text)
+                .chain_err(|| format!("failed to parse config file {}", path.display()))?
+        } else {
+            toml::from_str(&text)
+                .chain_err(|| format!("failed to parse config file {}", path.display()))?
+        };
+        layer.check_sources()?;
+        for (name, profile) in &layer.profiles {
+            if !profile.profiles.is_empty() {
+                bail!(ErrorKind::InvalidConfig(
+                    "profiles",
+                    format!("profile '{}' can't have profiles of its own", name)
+                ));
+            }
+            profile.check_sources()?;
+        }
+        Ok(layer)
+    }
+
+    /// Read a ConfigLayer from `CONWAY_*` environment variables, using `var` to look them up.
+    ///
+    /// `CONWAY_PLACE` can hold several placements, separated by `;`.
+    pub fn from_env<F: Fn(&str) -> Option<String>>(var: F) -> Result<Self> {
+        let layer = ConfigLayer {
+            delay: parse(var("CONWAY_DELAY").as_deref(), "CONWAY_DELAY", "an integer")?,
+            view: parse(var("CONWAY_VIEW").as_deref(), "CONWAY_VIEW", "a view")?,
+            live_char: parse(
+                var("CONWAY_LIVE_CHAR").as_deref(),
+                "CONWAY_LIVE_CHAR",
+                "a character",
+            )?,
+            dead_char: parse(
+                var("CONWAY_DEAD_CHAR").as_deref(),
+                "CONWAY_DEAD_CHAR",
+                "a character",
+            )?,
+            read_alive: var("CONWAY_READ_ALIVE"),
+            read_dead: var("CONWAY_READ_DEAD"),
+            output: parse(
+                var("CONWAY_OUTPUT").as_deref(),
+                "CONWAY_OUTPUT",
+                "an output format",
+            )?,
+            stats: parse(
+                var("CONWAY_STATS").as_deref(),
+                "CONWAY_STATS",
+                "true or false",
+            )?,
+            width: parse(var("CONWAY_WIDTH").as_deref(), "CONWAY_WIDTH", "an integer")?,
+            height: parse(
+                var("CONWAY_HEIGHT").as_deref(),
+                "CONWAY_HEIGHT",
+                "an integer",
+            )?,
+            pattern: var("CONWAY_PATTERN"),
+            file: var("CONWAY_FILE").map(PathBuf::from),
+            sample: var("CONWAY_SAMPLE"),
+            soup: var("CONWAY_SOUP"),
+            transpose: parse(
+                var("CONWAY_TRANSPOSE").as_deref(),
+                "CONWAY_TRANSPOSE",
+                "true or false",
+            )?,
+            flip: parse(var("CONWAY_FLIP").as_deref(), "CONWAY_FLIP", "h or v")?,
+            rotate: parse(
+                var("CONWAY_ROTATE").as_deref(),
+                "CONWAY_ROTATE",
+                "90, 180 or 270",
+            )?,
+            translate: parse(
+                var("CONWAY_TRANSLATE").as_deref(),
+                "CONWAY_TRANSLATE",
+                "a point like (x, y)",
+            )?,
+            place: match var("CONWAY_PLACE") {
+                Some(places) => Some(
+                    places
+                        .split(';')
+                        .filter(|place| !place.trim().is_empty())
+                        .map(str::parse)
+                        .collect::<Result<_>>()?,
+                ),
+                None => None,
+            },
+            profiles: BTreeMap::new(),
+        };
+        layer.check_sources()?;
+        Ok(layer)
+    }
+
+    /// Read a ConfigLayer from command line arguments. Arguments that weren't given are `None`.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        // "auto" leaves the size to the layers below, which fit the viewport to the pattern by
+        // default.
+        let size = |name| match matches.value_of(name) {
+            Some("auto") | None => Ok(None),
+            Some(value) => parse(Some(value), name, "an integer"),
+        };
+        let flag = |name| {
+            if matches.is_present(name) {
+                Some(true)
+            } else {
+                None
+            }
+        };
+
+        Ok(ConfigLayer {
+            delay: parse(matches.value_of("delay"), "delay", "an integer")?,
+            view: matches.value_of("view").map(str::parse).transpose()?,
+            live_char: parse(matches.value_of("live_char"), "live_char", "a character")?,
+            dead_char: parse(matches.value_of("dead_char"), "dead_char", "a character")?,
+            read_alive: matches.value_of("read_alive").map(str::to_string),
+            read_dead: matches.value_of("read_dead").map(str::to_string),
+            output: matches.value_of("output").map(str::parse).transpose()?,
+            stats: flag("stats"),
+            width: size("width")?,
+            height: size("height")?,
+            pattern: None,
+            file: matches.value_of("file").map(PathBuf::from),
+            sample: matches.value_of("sample").map(str::to_string),
+            soup: matches.value_of("soup").map(str::to_string),
+            transpose: flag("transpose"),
+            flip: matches.value_of("flip").map(str::parse).transpose()?,
+            rotate: matches.value_of("rotate").map(str::parse).transpose()?,
+            translate: matches.value_of("translate").map(str::parse).transpose()?,
+            place: match matches.values_of("place") {
+                Some(places) => Some(places.map(str::parse).collect::<Result<_>>()?),
+                None => None,
+            },
+            profiles: BTreeMap::new(),
+        })
+    }
+
+    /// Return this layer with the named profile applied on top of it.
+    pub fn with_profile(mut self, name: &str) -> Result<Self> {
+        match self.profiles.remove(name) {
+            Some(profile) => Ok(self.merge(profile)),
+            None => bail!(ErrorKind::InvalidConfig(
+                "profile",
+                format!("no profile named '{}' in the config file", name)
+            )),
+        }
+    }
+
+    /// Return this layer with `other` on top of it: fields given in `other` win.
+    pub fn merge(self, other: ConfigLayer) -> Self {
+        let (pattern, file, sample, soup) = if other.has_source() {
+            (other.pattern, other.file, other.sample, other.soup)
+        } else {
+            (self.pattern, self.file, self.sample, self.soup)
+        };
+        ConfigLayer {
+            delay: other.delay.or(self.delay),
+            view: other.view.or(self.view),
+            live_char: other.live_char.or(self.live_char),
+            dead_char: other.dead_char.or(self.dead_char),
+            read_alive: other.read_alive.or(self.read_alive),
+            read_dead: other.read_dead.or(self.read_dead),
+            output: other.output.or(self.output),
+            stats: other.stats.or(self.stats),
+            width: other.width.or(self.width),
+            height: other.height.or(self.height),
+            pattern,
+            file,
+            sample,
+            soup,
+            transpose: other.transpose.or(self.transpose),
+            flip: other.flip.or(self.flip),
+            rotate: other.rotate.or(self.rotate),
+            translate: other.translate.or(self.translate),
+            place: other.place.or(self.place),
+            profiles: self.profiles,
+        }
+    }
+
+    /// Return the Settings given in the layer, as an update to apply on top of other Settings.
+    pub fn settings_update(&self) -> SettingsUpdate {
+        SettingsUpdate {
+            delay: self.delay.map(Duration::from_millis),
+            view: self.view.clone(),
+            char_alive: self.live_char,
+            char_dead: self.dead_char,
+        }
+    }
+
+    /// Return the output options given in the layer, with defaults for anything it doesn't give.
+    pub fn output_options(&self) -> OutputOptions {
+        OutputOptions {
+            format: self.output.clone().unwrap_or_default(),
+            show_stats: self.stats.unwrap_or(false),
+        }
+    }
+
+    /// Build a GameConfig from the layer, with built-in defaults for anything it doesn't give.
+    pub fn into_game_config(self) -> Result<GameConfig> {
+        if !self.has_source() && self.place.is_none() {
+            bail!("no pattern given: use --file, --sample, --soup or --place");
+        }
+        // The command line has always defaulted to a fixed view.
+        let settings = self.settings_update().apply(&Settings {
+            view: View::Fixed,
+            ..Settings::default()
+        });
+        let soup: Option<SoupSpec> = self.soup.as_ref().map(|s| s.parse()).transpose()?;
+        let transform = Transform {
+            transpose: self.transpose.unwrap_or(false),
+            flip: self.flip,
+            rotate: self.rotate,
+            translate: self.translate.unwrap_or_default(),
+        };
+        // Soups are sized to fit the whole soup by default, not just its live cells.
+        let soup_size = soup.as_ref().map(|soup| {
+            if transform.swaps_axes() {
+                (soup.height, soup.width)
+            } else {
+                (soup.width, soup.height)
+            }
+        });
+
+        // Soups and samples are always in the standard format.
+        let alphabet = if self.pattern.is_some() || self.file.is_some() {
+            let default = Alphabet::default();
+            Alphabet::new(
+                self.read_alive.as_ref().unwrap_or(&default.alive),
+                self.read_dead.as_ref().unwrap_or(&default.dead),
+            )
+        } else {
+            Alphabet::default()
+        };
+        let pattern = if let Some(pattern) = self.pattern {
+            pattern
+        } else if let Some(file) = self.file {
+            read_to_string(&file)
+                .chain_err(|| format!("failed to read pattern file {}", file.display()))?
+        } else if let Some(ref soup) = soup {
+            soup.generate().to_string()
+        } else if let Some(sample) = self.sample {
+            match SAMPLES.get(&sample) {
+                Some(entry) => entry.source.clone(),
+                None => bail!(ErrorKind::InvalidConfig(
+                    "sample",
+                    format!("unknown sample pattern '{}'", sample)
+                )),
+            }
+        } else {
+            String::new()
+        };
+
+        Ok(GameConfig {
+            settings,
+            pattern,
+            alphabet,
+            bounds: (
+                self.width.or_else(|| soup_size.map(|size| size.0)),
+                self.height.or_else(|| soup_size.map(|size| size.1)),
+            ),
+            transform,
+            scene: self.place.unwrap_or_default(),
+        })
+    }
+
+    fn has_source(&self) -> bool {
+        self.pattern.is_some()
+            || self.file.is_some()
+            || self.sample.is_some()
+            || self.soup.is_some()
+    }
+
+    fn check_sources(&self) -> Result<()> {
+        let sources = [
+            self.pattern.is_some(),
+            self.file.is_some(),
+            self.sample.is_some(),
+            self.soup.is_some(),
+        ];
+        if sources.iter().filter(|&&given| given).count() > 1 {
+            bail!(ErrorKind::InvalidConfig(
+                "pattern",
+                "only one of pattern, file, sample and soup can be given".to_string()
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Parse an optional value, reporting errors against the option or variable it came from.
+fn parse<T: FromStr>(
+    value: Option<&str>,
+    name: &'static str,
+    expected: &'static str,
+) -> Result<Option<T>> {
+    match value {
+        Some(value) => match value.trim().parse() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => bail!(ErrorKind::ParseArg(name, expected)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Return the user's config directory for conway: `$XDG_CONFIG_HOME/conway`, or
+/// `~/.config/conway` if that isn't set.
+pub fn config_dir<F: Fn(&str) -> Option<String>>(var: F) -> Option<PathBuf> {
+    let dir = match (var("XDG_CONFIG_HOME"), var("HOME")) {
+        (Some(dir), _) if !dir.is_empty() => PathBuf::from(dir),
+        (_, Some(home)) => Path::new(&home).join(".config"),
+        _ => return None,
+    };
+    Some(dir.join("conway"))
+}
+
+/// Return the path of the user's config file, `config.toml` or `config.json` in the config
+/// directory, if there is one.
+pub fn find_config_file<F: Fn(&str) -> Option<String>>(var: F) -> Option<PathBuf> {
+    let dir = config_dir(var)?;
+    ["config.toml", "config.json"]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config::Command;
+    use grid::Grid;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+
+    // Return the scratch directory for the test.
+    fn scratch_dir(test: &str) -> PathBuf {
+        env::temp_dir().join(format!("conway-{}-{}", test, ::std::process::id()))
+    }
+
+    // Write `contents` to a file in the scratch directory for the test, and return its path.
+    fn write_file(test: &str, name: &str, contents: &str) -> PathBuf {
+        let path = scratch_dir(test).join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn run_command(args: &[&str], vars: &[(&str, &str)]) -> Result<Command> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let args = Some("conway").into_iter().chain(args.iter().cloned());
+        Command::from_args_with_env(args, |name| vars.get(name).cloned())
+    }
+
+    fn run_config(args: &[&str], vars: &[(&str, &str)]) -> Result<GameConfig> {
+        match run_command(args, vars)? {
+            Command::Run { config, .. } => Ok(config),
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    fn run_output(args: &[&str], vars: &[(&str, &str)]) -> OutputOptions {
+        match run_command(args, vars).unwrap() {
+            Command::Run { output, .. } => output,
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = ConfigLayer {
+            delay: Some(100),
+            width: Some(10),
+            sample: Some("glider".to_string()),
+            ..Default::default()
+        };
+        let merged = base.clone().merge(ConfigLayer {
+            delay: Some(200),
+            soup: Some("8x8:0.5:1".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(merged.delay, Some(200));
+        assert_eq!(merged.width, Some(10));
+        assert_eq!(
+            merged.sample, None,
+            "a new source should replace the old one"
+        );
+        assert_eq!(merged.soup, Some("8x8:0.5:1".to_string()));
+        assert_eq!(base.clone().merge(ConfigLayer::default()), base);
+    }
+
+    #[test]
+    fn test_read_alphabet() {
+        let path = write_file("read-alphabet", "drawn", "-#-\n--#\n###\n");
+        let path = path.to_str().unwrap();
+        let glider: Grid = ".x.\n..x\nxxx".parse().unwrap();
+
+        let config =
+            run_config(&["-F", path, "--read-alive", "#", "--read-dead", "-"], &[]).unwrap();
+        assert_eq!(config.alphabet.comment, None);
+        assert_eq!(config.build().unwrap().grid(), &glider);
+
+        assert!(run_config(&["-F", path], &[]).unwrap().build().is_err());
+
+        let config = run_config(&["-S", "glider"], &[("CONWAY_READ_ALIVE", "#")]).unwrap();
+        assert_eq!(
+            config.alphabet,
+            Alphabet::default(),
+            "samples use the standard format"
+        );
+
+        fs::remove_dir_all(scratch_dir("read-alphabet")).unwrap();
+    }
+
+    #[test]
+    fn test_precedence() {
+        let path = write_file(
+            "precedence",
+            "config.toml",
+            r#"
+            delay = 100
+            live_char = "o"
+            dead_char = "."
+            sample = "toad"
+
+            [profiles.fast]
+            delay = 10
+            output = "json"
+            "#,
+        );
+        let path = path.to_str().unwrap();
+
+        let config = run_config(&["-c", path], &[]).unwrap();
+        assert_eq!(config.settings.delay, Duration::from_millis(100));
+        assert_eq!(config.settings.char_alive, 'o');
+        assert_eq!(config.settings.view, View::Fixed, "should use the defaults");
+        assert_eq!(config.pattern, SAMPLES.get("toad").unwrap().source);
+
+        let config = run_config(
+            &["-c", path, "--profile", "fast", "-o", "@"],
+            &[("CONWAY_DELAY", "50"), ("CONWAY_LIVE_CHAR", "*")],
+        )
+        .unwrap();
+        assert_eq!(config.settings.delay, Duration::from_millis(50));
+        assert_eq!(config.settings.char_alive, '@');
+        assert_eq!(config.settings.char_dead, '.');
+
+        let config = run_config(
+            &["-d", "5", "-S", "glider"],
+            &[("CONWAY_CONFIG", path), ("CONWAY_PROFILE", "fast")],
+        )
+        .unwrap();
+        assert_eq!(config.settings.delay, Duration::from_millis(5));
+        assert_eq!(config.pattern, SAMPLES.get("glider").unwrap().source);
+
+        let vars = [("CONWAY_CONFIG", path), ("CONWAY_PROFILE", "fast")];
+        assert_eq!(run_output(&["-S", "glider"], &vars).format, Output::Json);
+        assert_eq!(
+            run_output(&["-S", "glider", "--stats"], &[]),
+            OutputOptions {
+                format: Output::Text,
+                show_stats: true,
+            }
+        );
+
+        assert!(run_config(&["-c", path, "--profile", "slow"], &[]).is_err());
+        assert!(run_config(&["-S", "glider"], &[("CONWAY_DELAY", "soon")]).is_err());
+        assert!(run_config(&["-c", "/nonexistent/conway.toml", "-S", "glider"], &[]).is_err());
+
+        fs::remove_dir_all(scratch_dir("precedence")).unwrap();
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = write_file(
+            "from-file",
+            "config.json",
+            r#"{
+                "stats": true,
+                "rotate": "90",
+                "place": [{"source": {"sample": "blinker"}, "offset": [4, 4]}]
+            }"#,
+        );
+        let layer = ConfigLayer::from_file(&path).unwrap();
+        assert_eq!(layer.stats, Some(true));
+        assert_eq!(layer.rotate, Some(Rotation::R90));
+        assert_eq!(layer.place.as_ref().map(Vec::len), Some(1));
+        assert!(layer.into_game_config().is_ok());
+
+        for (name, contents) in &[
+            ("typo.toml", "dealy = 100"),
+            ("sources.toml", "sample = \"glider\"\nsoup = \"8x8:0.5:1\""),
+            ("nested.toml", "[profiles.a.profiles.b]\ndelay = 1"),
+        ] {
+            let path = write_file("from-file", name, contents);
+            assert!(ConfigLayer::from_file(&path).is_err(), "{}", name);
+        }
+
+        fs::remove_dir_all(scratch_dir("from-file")).unwrap();
+    }
+
+    #[test]
+    fn test_find_config_file() {
+        let path = write_file("find", "conway/config.toml", "");
+        let config_home = path.parent().unwrap().parent().unwrap();
+        let found = find_config_file(|name| match name {
+            "XDG_CONFIG_HOME" => Some(config_home.to_str().unwrap().to_string()),
+            _ => None,
+        });
+        assert_eq!(found, Some(path));
+        assert_eq!(find_config_file(|_| None), None);
+
+        fs::remove_dir_all(scratch_dir("find")).unwrap();
+    }
+}
@@ -7,31 +7,59 @@ extern crate clap;
 extern crate error_chain;
 #[macro_use]
 extern crate lazy_static;
+#[cfg(test)]
 #[macro_use]
 extern crate maplit;
 #[macro_use]
 extern crate serde_derive;
+extern crate gif;
 extern crate num_integer;
+extern crate png;
 extern crate serde;
 extern crate serde_json;
+extern crate toml;
 
+pub mod alphabet;
+pub mod catalog;
+pub mod census;
+pub mod checkpoint;
 pub mod config;
 pub mod game;
 pub mod grid;
+pub mod history;
+pub mod image;
+pub mod interactive;
+pub mod layer;
+pub mod pattern;
 pub mod point;
+pub mod scene;
+pub mod soup;
+pub mod stats;
+pub mod transform;
 
+pub use alphabet::Alphabet;
+pub use catalog::{PatternCatalog, PatternMeta};
 pub use config::GameConfig;
 pub use errors::*;
-pub use game::{Game, View};
-pub use grid::Grid;
+pub use game::{Game, Output, OutputOptions, View};
+pub use grid::{Cluster, Grid, StampMode};
+pub use history::History;
+pub use image::{Animation, AnimationOptions, Color, Image, ImageOptions, Region};
+pub use pattern::{ParseError, Pattern};
 pub use point::Point;
+pub use scene::{LocalResolver, PatternResolver, PatternSource, Placement};
+pub use soup::{SoupSpec, Symmetry};
+pub use stats::Stats;
+pub use transform::{Flip, Rotation, Transform};
 
 mod errors {
+    use pattern::ParseError;
+
     error_chain! {
         errors {
-            ParseGrid(s: String) {
+            ParseGrid(errors: Vec<ParseError>) {
                 description("failed to parse Grid"),
-                display("failed to parse Grid: {}", s),
+                display("failed to parse Grid at {}", ParseError::summarize(errors)),
             }
             ParsePoint(s: String) {
                 description("failed to parse Point"),
@@ -41,10 +69,20 @@ mod errors {
                 description("failed to parse argument"),
                 display("failed to parse argument '{}': expected {}", arg, expected),
             }
+            InvalidConfig(field: &'static str, reason: String) {
+                description("invalid config"),
+                display("invalid value for '{}': {}", field, reason),
+            }
+            HistoryUnavailable(generation: u64) {
+                description("generation is no longer in the history"),
+                display("generation {} is no longer in the history", generation),
+            }
         }
 
         foreign_links {
             IO(::std::io::Error);
+            Png(::png::EncodingError);
+            Gif(::gif::EncodingError);
         }
     }
 
@@ -59,6 +97,11 @@ mod errors {
             let errmsg = "Well, shit. Encountered an error while trying to write another error. \
                           Good luck trying to figure it out!";
             writeln!(output, "error: {}", self).expect(errmsg);
+            if let ErrorKind::ParseGrid(ref errors) = *self.kind() {
+                for err in errors {
+                    writeln!(output, "{}", err.render()).expect(errmsg);
+                }
+            }
 
             for err in self.iter().skip(1) {
                 writeln!(output, "caused by: {}", err).expect(errmsg);
@@ -1,9 +1,18 @@
 extern crate conway;
+extern crate ctrlc;
 
 use std::io;
 use std::io::prelude::*;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use conway::{GameConfig, Result};
+use conway::catalog::SAMPLES;
+use conway::census::CensusConfig;
+use conway::checkpoint::Checkpoint;
+use conway::config::{Command, ImageExport};
+use conway::interactive::run_interactive;
+use conway::{Animation, Game, Output, OutputOptions, Result};
 
 fn main() {
     if let Err(ref e) = run() {
@@ -14,11 +23,122 @@ fn main() {
 }
 
 fn run() -> Result<()> {
-    let mut game = GameConfig::from_argv()?.build()?;
+    match Command::from_argv()? {
+        Command::Run {
+            config,
+            output,
+            checkpoint,
+            image,
+            interactive,
+        } => start_game(config.build()?, &output, checkpoint, image, interactive),
+        Command::Resume {
+            path,
+            settings,
+            output,
+            checkpoint,
+            image,
+            interactive,
+        } => {
+            let mut game = Game::load(&path)?;
+            game.update_settings(&settings)?;
+            start_game(game, &output, checkpoint, image, interactive)
+        }
+        Command::ListSamples => {
+            print!("{}", *SAMPLES);
+            Ok(())
+        }
+        Command::Census {
+            config,
+            rare,
+            save_dir,
+        } => run_census(&config, rare, &save_dir),
+    }
+}
+
+fn start_game(
+    mut game: Game,
+    output: &OutputOptions,
+    checkpoint: Option<Checkpoint>,
+    image: Option<ImageExport>,
+    interactive: bool,
+) -> Result<()> {
+    match image {
+        Some(image) => draw_game(game, &image),
+        None if interactive => {
+            let stdin = io::stdin();
+            run_interactive(&mut game, stdin.lock(), io::stdout())
+        }
+        None => run_game(game, output, checkpoint),
+    }
+}
+
+fn draw_game(mut game: Game, image: &ImageExport) -> Result<()> {
+    match *image {
+        ImageExport::Png {
+            ref path,
+            generation,
+            ref options,
+        } => {
+            if let Some(generation) = generation {
+                game.seek(generation)?;
+            }
+            game.to_image(options)?.save_png(path)
+        }
+        ImageExport::Gif {
+            ref path,
+            ref options,
+        } => Animation::record(&mut game, options)?.save_gif(path),
+    }
+}
+
+fn run_game(
+    mut game: Game,
+    output: &OutputOptions,
+    mut checkpoint: Option<Checkpoint>,
+) -> Result<()> {
     let mut stdout = io::stdout();
-    for frame in game.iter().with_delay(true) {
-        write!(stdout, "\n{}", frame)?;
+    // Json output is one frame per line, so it doesn't need blank lines between frames.
+    let separator = match output.format {
+        Output::Text => "\n",
+        Output::Json => "",
+    };
+    // When checkpointing, stop at the end of the current generation on SIGINT or SIGTERM, so the
+    // last snapshot is of a whole generation.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    if checkpoint.is_some() {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .map_err(|err| err.to_string())?;
+    }
+    while !game.is_over() && !interrupted.load(Ordering::SeqCst) {
+        game.tick_with_delay();
+        write!(stdout, "{}{}", separator, game.render(output))?;
         stdout.flush()?;
+        if let Some(ref mut checkpoint) = checkpoint {
+            checkpoint.update(&game)?;
+        }
+    }
+    if let Some(ref mut checkpoint) = checkpoint {
+        checkpoint.save(&game)?;
+        eprintln!(
+            "saved generation {} to {}",
+            game.generation(),
+            checkpoint.path.display()
+        );
+    }
+    Ok(())
+}
+
+fn run_census(config: &CensusConfig, rare: u64, save_dir: &Path) -> Result<()> {
+    let report = config.run()?;
+    print!("{}", report);
+    let saved = report.save_rare(save_dir, rare)?;
+    if !saved.is_empty() {
+        println!(
+            "saved {} soups with rare objects to {}",
+            saved.len(),
+            save_dir.display()
+        );
     }
     Ok(())
 }
@@ -0,0 +1,497 @@
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use alphabet::Alphabet;
+use grid::{Grid, Point};
+use {Error, ErrorKind, Result};
+
+/// Parsing stops after this many errors.
+pub const MAX_PARSE_ERRORS: usize = 100;
+/// Snippets of longer lines are cut down to this many characters around the error.
+pub const MAX_SNIPPET_LEN: usize = 80;
+
+/// A ParseError is a problem found while parsing a Pattern, at a line and column (both 1-based)
+/// of the source text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    /// The line of source text that the error is on. Long lines are cut down to the part around
+    /// the error, with `...` marking what was cut off.
+    pub snippet: String,
+    /// The column of the error within `snippet` (1-based).
+    pub snippet_column: usize,
+}
+
+impl ParseError {
+    /// Return a description of the error, followed by its snippet with a caret under the column,
+    /// for showing in a terminal.
+    pub fn render(&self) -> String {
+        let gutter = " ".repeat(self.line.to_string().len());
+        // Copy tabs so that the caret lines up with the snippet however tabs are shown.
+        let indent: String = self
+            .snippet
+            .chars()
+            .take(self.snippet_column - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!(
+            "{}--> {}\n{} |\n{} | {}\n{} | {}^",
+            gutter, self, gutter, self.line, self.snippet, gutter, indent
+        )
+    }
+
+    /// Describe a list of errors in one line: the first error, and how many more there are.
+    pub fn summarize(errors: &[ParseError]) -> String {
+        match errors.len() {
+            0 => "no errors".to_string(),
+            1 => errors[0].to_string(),
+            n => format!("{} (and {} more)", errors[0], n - 1),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// A Pattern is a Grid together with the information from the comments in its file.
+///
+/// Comments of the form `# Key: value` are read into the matching field, for the keys `Name`,
+/// `Description`, `Author`, `Rule` and `Origin`. A comment on the first line without a key is
+/// also taken as the name. Every other comment, including those at the end of a row, is kept in
+/// `comments`. For example:
+///
+/// ```text
+/// # Glider
+/// # Author: Richard K. Guy
+/// # Origin: (10, -4)
+/// # Type: Spaceship
+/// .x.
+/// ..x
+/// xxx
+/// ```
+///
+/// Writing a Pattern with `Display` puts the fields and comments back in a header, so it parses
+/// back into the same Pattern.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pattern {
+    pub name: Option<String>,
+    /// The description, which may span several lines.
+    pub description: Option<String>,
+    pub author: Option<String>,
+    /// The rule the pattern is meant for, e.g. `B3/S23`. It isn't used by the Game.
+    pub rule: Option<String>,
+    /// Where the top-left corner of the pattern goes.
+    pub origin: Point,
+    pub comments: Vec<String>,
+    /// The pattern's cells, with its top-left corner at the origin.
+    pub grid: Grid,
+}
+
+impl Pattern {
+    /// Create a Pattern with no metadata from the cells of a Grid, keeping their position.
+    pub fn new(grid: &Grid) -> Self {
+        Pattern {
+            origin: grid.bounds().0,
+            grid: grid.at_origin(),
+            ..Default::default()
+        }
+    }
+
+    /// Return the pattern's cells, moved to its origin.
+    pub fn placed(&self) -> Grid {
+        self.grid.translated(self.origin)
+    }
+
+    /// Return the value of the first comment of the form `key: value`, ignoring case.
+    ///
+    /// This is how to read keys that don't have a field of their own, like `Type` or `Period`.
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.comments
+            .iter()
+            .filter_map(|comment| split_key(comment))
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Parse a Pattern from text written with the given Alphabet.
+    pub fn parse_with(s: &str, alphabet: &Alphabet) -> Result<Self> {
+        let mut pattern = Pattern::default();
+        let mut cells = Vec::new();
+
+        let lines: Vec<&str> = s.lines().collect();
+        let mut errors = Vec::new();
+
+        // Leading whitespace is trimmed off, so keep track of how much there was in order to
+        // report error locations relative to the original text.
+        let skipped = &s[..s.len() - s.trim_start().len()];
+        let skipped_lines = skipped.matches('\n').count();
+        let skipped_columns = skipped[skipped.rfind('\n').map_or(0, |i| i + 1)..]
+            .chars()
+            .count();
+        let error_at = |i: usize, x: usize, message: String| {
+            let column = x + 1 + if i == 0 { skipped_columns } else { 0 };
+            let (snippet, snippet_column) = snippet(lines[skipped_lines + i], column);
+            ParseError {
+                line: skipped_lines + i + 1,
+                column,
+                message,
+                snippet,
+                snippet_column,
+            }
+        };
+
+        // Where the origin was last set, to report it if it puts the pattern out of range.
+        let mut origin_at = (0, 0);
+        let mut y = 0;
+        for (i, line) in s.trim().lines().enumerate() {
+            if errors.len() >= MAX_PARSE_ERRORS {
+                break;
+            }
+            if line.starts_with(|ch| alphabet.is_comment(ch)) {
+                let comment = &line[line.chars().next().unwrap().len_utf8()..];
+                let origin = pattern.origin;
+                if let Err(reason) = pattern.add_comment(comment, i == 0) {
+                    errors.push(error_at(i, 0, reason));
+                } else if pattern.origin != origin {
+                    origin_at = (i, 0);
+                }
+                continue;
+            }
+            for (x, (j, ch)) in line.char_indices().enumerate() {
+                match ch {
+                    // Living Points are added to the Grid.
+                    ch if alphabet.is_alive(ch) => cells.push(Point(x as i64, y)),
+                    // Dead Points are ignored.
+                    ch if alphabet.is_dead(ch) => (),
+                    // The rest of the line after a comment char is a comment.
+                    ch if alphabet.is_comment(ch) => {
+                        let comment = &line[j + ch.len_utf8()..];
+                        let origin = pattern.origin;
+                        if let Err(reason) = pattern.add_comment(comment, i == 0) {
+                            errors.push(error_at(i, x, reason));
+                        } else if pattern.origin != origin {
+                            origin_at = (i, x);
+                        }
+                        break;
+                    }
+                    // Anything else is invalid.
+                    _ => errors.push(error_at(
+                        i,
+                        x,
+                        format!("unknown character: '{}'", ch.escape_default()),
+                    )),
+                };
+            }
+            y += 1;
+        }
+
+        if !errors.is_empty() {
+            errors.truncate(MAX_PARSE_ERRORS);
+            bail!(ErrorKind::ParseGrid(errors));
+        }
+
+        // Blank columns to the left of the pattern are kept as part of its origin. Every cell has
+        // to stay in range once the pattern is moved there.
+        let grid = Grid::new(cells);
+        let (min, max) = grid.bounds();
+        match pattern.origin.checked_add(min) {
+            Some(origin) if origin.checked_add(max - min).is_some() => pattern.origin = origin,
+            _ => {
+                let (i, x) = origin_at;
+                bail!(ErrorKind::ParseGrid(vec![error_at(
+                    i,
+                    x,
+                    format!("origin {} puts the pattern out of range", pattern.origin),
+                )]));
+            }
+        }
+        pattern.grid = grid.at_origin();
+        Ok(pattern)
+    }
+
+    /// Write the Pattern with the given Alphabet: a header of comments followed by its rows. If
+    /// the Alphabet has no comment character, only the rows are written.
+    pub fn to_string_with(&self, alphabet: &Alphabet) -> String {
+        let mut s = String::new();
+        self.write_with(&mut s, alphabet)
+            .expect("failed to write to a String");
+        s
+    }
+
+    fn write_with<W: Write>(&self, s: &mut W, alphabet: &Alphabet) -> fmt::Result {
+        let comment = match alphabet.comment {
+            Some(comment) => comment,
+            None => return s.write_str(&self.grid.to_string_with(alphabet)),
+        };
+        match self.name {
+            // A name that looks like a key has to be written with one.
+            Some(ref name) if split_key(name).is_some() => {
+                writeln!(s, "{} Name: {}", comment, name)?
+            }
+            Some(ref name) => writeln!(s, "{} {}", comment, name)?,
+            // Otherwise a comment on the first line would be read back as the name.
+            None => writeln!(s, "{}", comment)?,
+        }
+        if let Some(ref description) = self.description {
+            for line in description.lines() {
+                writeln!(s, "{} Description: {}", comment, line)?;
+            }
+        }
+        if let Some(ref author) = self.author {
+            writeln!(s, "{} Author: {}", comment, author)?;
+        }
+        if let Some(ref rule) = self.rule {
+            writeln!(s, "{} Rule: {}", comment, rule)?;
+        }
+        if self.origin != Point::origin() {
+            writeln!(s, "{} Origin: {}", comment, self.origin)?;
+        }
+        for text in &self.comments {
+            writeln!(s, "{} {}", comment, text)?;
+        }
+        s.write_str(&self.grid.to_string_with(alphabet))
+    }
+
+    // Read a comment into the matching field, or add it to the list of comments.
+    fn add_comment(
+        &mut self,
+        comment: &str,
+        first_line: bool,
+    ) -> ::std::result::Result<(), String> {
+        let comment = comment.trim();
+        match split_key(comment) {
+            Some((key, value)) => match key.to_lowercase().as_str() {
+                "name" => self.name = Some(value.to_string()),
+                "description" => {
+                    self.description = Some(match self.description.take() {
+                        Some(description) => format!("{}\n{}", description, value),
+                        None => value.to_string(),
+                    })
+                }
+                "author" => self.author = Some(value.to_string()),
+                "rule" => self.rule = Some(value.to_string()),
+                "origin" => {
+                    self.origin = value
+                        .parse()
+                        .map_err(|_| format!("invalid origin '{}'", value))?
+                }
+                _ => self.comments.push(comment.to_string()),
+            },
+            None if comment.is_empty() => (),
+            None if first_line && self.name.is_none() => self.name = Some(comment.to_string()),
+            None => self.comments.push(comment.to_string()),
+        }
+        Ok(())
+    }
+}
+
+// Cut a line down to at most MAX_SNIPPET_LEN characters around the given column, and return it
+// along with the column's position in it.
+fn snippet(line: &str, column: usize) -> (String, usize) {
+    let len = line.chars().count();
+    if len <= MAX_SNIPPET_LEN {
+        return (line.to_string(), column);
+    }
+    let start = (column - 1)
+        .saturating_sub(MAX_SNIPPET_LEN / 2)
+        .min(len - MAX_SNIPPET_LEN);
+    let mut snippet = if start > 0 {
+        "...".to_string()
+    } else {
+        String::new()
+    };
+    let column = column - start + snippet.len();
+    snippet.extend(line.chars().skip(start).take(MAX_SNIPPET_LEN));
+    if start + MAX_SNIPPET_LEN < len {
+        snippet.push_str("...");
+    }
+    (snippet, column)
+}
+
+// Split a comment like `Key: value` into its key and value. The key must be a single word.
+fn split_key(comment: &str) -> Option<(&str, &str)> {
+    let mut parts = comment.splitn(2, ':');
+    match (parts.next().map(str::trim), parts.next().map(str::trim)) {
+        (Some(key), Some(value))
+            if !key.is_empty() && key.chars().all(|ch| ch.is_ascii_alphanumeric()) =>
+        {
+            Some((key, value))
+        }
+        _ => None,
+    }
+}
+
+/// Write the Pattern as a header of comments followed by its rows.
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_with(f, &Alphabet::default())
+    }
+}
+
+/// Parse a Pattern from a block of structured text.
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Pattern::parse_with(s, &Alphabet::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TOAD: &str = "# Toad
+# Description: A period 2 oscillator.
+# Description: Found in 1970.
+# Author: Simon Norton
+# Rule: B3/S23
+# Origin: (3, -1)
+# Type: Oscillator
+.xxx# top row
+xxx.
+";
+
+    #[test]
+    fn test_from_str() {
+        let pattern: Pattern = TOAD.parse().unwrap();
+        assert_eq!(
+            pattern,
+            Pattern {
+                name: Some("Toad".to_string()),
+                description: Some("A period 2 oscillator.\nFound in 1970.".to_string()),
+                author: Some("Simon Norton".to_string()),
+                rule: Some("B3/S23".to_string()),
+                origin: Point(3, -1),
+                comments: vec!["Type: Oscillator".to_string(), "top row".to_string()],
+                grid: ".xxx\nxxx.".parse::<Grid>().unwrap(),
+            }
+        );
+        assert_eq!(pattern.field("type"), Some("Oscillator"));
+        assert_eq!(pattern.field("period"), None);
+        assert_eq!(
+            pattern.placed(),
+            Grid::new(vec![
+                Point(4, -1),
+                Point(5, -1),
+                Point(6, -1),
+                Point(3, 0),
+                Point(4, 0),
+                Point(5, 0)
+            ])
+        );
+
+        // Only an unkeyed comment on the first line is a name.
+        let pattern: Pattern = "xx\n# Glider\n# Glider gun: not a key".parse().unwrap();
+        assert_eq!(pattern.name, None);
+        assert_eq!(pattern.comments, vec!["Glider", "Glider gun: not a key"]);
+        assert_eq!(pattern.field("glider gun"), None);
+
+        match "x\n# Origin: 1, 2".parse::<Pattern>().unwrap_err().kind() {
+            ErrorKind::ParseGrid(errors) => assert_eq!((errors[0].line, errors[0].column), (2, 1)),
+            kind => panic!("unexpected error: {}", kind),
+        }
+
+        let far = "# Origin: (9223372036854775807, 0)\nxx";
+        match far.parse::<Pattern>().unwrap_err().kind() {
+            ErrorKind::ParseGrid(errors) => {
+                assert_eq!((errors[0].line, errors[0].column), (1, 1));
+                assert!(errors[0].message.contains("out of range"));
+            }
+            kind => panic!("unexpected error: {}", kind),
+        }
+        assert!(far.parse::<Grid>().is_err());
+        let edge: Pattern = "# Origin: (9223372036854775806, 0)\nxx".parse().unwrap();
+        assert_eq!(edge.placed().bounds().1, Point(i64::MAX, 0));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let pattern: Pattern = TOAD.parse().unwrap();
+        assert_eq!(pattern.to_string().parse::<Pattern>().unwrap(), pattern);
+
+        let alphabet = Alphabet::new("o■", "-");
+        let text = pattern.to_string_with(&alphabet);
+        assert!(text.starts_with("# Toad\n") && text.ends_with("-ooo\nooo-\n"));
+        assert_eq!(Pattern::parse_with(&text, &alphabet).unwrap(), pattern);
+        assert_eq!(
+            pattern.to_string_with(&Alphabet::new("#", "-")),
+            "-###\n###-\n",
+            "there's nowhere to write the header without comments"
+        );
+
+        for pattern in &[
+            Pattern::default(),
+            Pattern {
+                comments: vec!["not a name".to_string()],
+                ..Pattern::new(&Grid::new(vec![Point(-2, 5)]))
+            },
+            Pattern {
+                name: Some("Name: tricky".to_string()),
+                ..Default::default()
+            },
+        ] {
+            assert_eq!(
+                &pattern.to_string().parse::<Pattern>().unwrap(),
+                pattern,
+                "{}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let errors = match "# Test\nx.o\n\t?x\n.x#ok\n# Origin: here"
+            .parse::<Pattern>()
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::ParseGrid(errors) => errors.clone(),
+            kind => panic!("unexpected error: {}", kind),
+        };
+        let locations: Vec<_> = errors.iter().map(|e| (e.line, e.column)).collect();
+        assert_eq!(locations, vec![(2, 3), (3, 1), (3, 2), (5, 1)]);
+        assert_eq!(errors[0].snippet, "x.o");
+        assert_eq!(errors[0].message, "unknown character: 'o'");
+        assert_eq!(
+            errors[0].render(),
+            " --> line 2, column 3: unknown character: 'o'\n  |\n2 | x.o\n  |   ^"
+        );
+        assert_eq!(errors[2].render().lines().last(), Some("  | \t^"));
+        assert_eq!(
+            ParseError::summarize(&errors),
+            "line 2, column 3: unknown character: 'o' (and 3 more)"
+        );
+
+        let many = "o".repeat(MAX_PARSE_ERRORS * 2);
+        match many.parse::<Pattern>().unwrap_err().kind() {
+            ErrorKind::ParseGrid(errors) => assert_eq!(errors.len(), MAX_PARSE_ERRORS),
+            kind => panic!("unexpected error: {}", kind),
+        }
+
+        // Only the part of a long line around the error is kept.
+        let long = format!("{}?{}", "x".repeat(200), "x".repeat(200));
+        let error = match long.parse::<Pattern>().unwrap_err().kind() {
+            ErrorKind::ParseGrid(errors) => errors[0].clone(),
+            kind => panic!("unexpected error: {}", kind),
+        };
+        assert_eq!(error.column, 201);
+        assert_eq!(error.snippet.chars().count(), MAX_SNIPPET_LEN + 6);
+        assert!(error.snippet.starts_with("...x") && error.snippet.ends_with("x..."));
+        let render = error.render();
+        let lines: Vec<&str> = render.lines().collect();
+        assert_eq!(lines[2].find('?'), lines[3].find('^'));
+    }
+}
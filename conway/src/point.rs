@@ -2,16 +2,51 @@ use std::fmt;
 use std::ops;
 use std::str::FromStr;
 
+use transform::{Flip, Rotation};
 use {Error, ErrorKind, Result};
 
 /// A Point represents an (x, y) coordinate on the `Grid`.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Point(pub i64, pub i64);
 
 impl Point {
     pub fn origin() -> Point {
         Point(0, 0)
     }
+
+    /// Return the Point moved by the given offset.
+    pub fn translated(self, by: Point) -> Point {
+        self + by
+    }
+
+    /// Return the Point moved by the given offset, or `None` if either coordinate overflows.
+    pub fn checked_add(self, by: Point) -> Option<Point> {
+        Some(Point(self.0.checked_add(by.0)?, self.1.checked_add(by.1)?))
+    }
+
+    /// Return the Point rotated clockwise around the origin. The Y axis points down.
+    pub fn rotated(self, rotation: Rotation) -> Point {
+        let Point(x, y) = self;
+        match rotation {
+            Rotation::R90 => Point(-y, x),
+            Rotation::R180 => Point(-x, -y),
+            Rotation::R270 => Point(y, -x),
+        }
+    }
+
+    /// Return the Point mirrored across the Y axis (`Horizontal`) or the X axis (`Vertical`).
+    pub fn flipped(self, flip: Flip) -> Point {
+        let Point(x, y) = self;
+        match flip {
+            Flip::Horizontal => Point(-x, y),
+            Flip::Vertical => Point(x, -y),
+        }
+    }
+
+    /// Return the Point with its X and Y coordinates swapped.
+    pub fn transposed(self) -> Point {
+        Point(self.1, self.0)
+    }
 }
 
 impl ops::Add for Point {
@@ -113,6 +148,18 @@ mod test {
         assert_eq!(Point(-3, 5) - Point(-5, -6), Point(2, 11));
     }
 
+    #[test]
+    fn test_transforms() {
+        let p = Point(2, -5);
+        assert_eq!(p.translated(Point(-1, 1)), Point(1, -4));
+        assert_eq!(p.rotated(Rotation::R90), Point(5, 2));
+        assert_eq!(p.rotated(Rotation::R180), Point(-2, 5));
+        assert_eq!(p.rotated(Rotation::R270), Point(-5, -2));
+        assert_eq!(p.flipped(Flip::Horizontal), Point(-2, -5));
+        assert_eq!(p.flipped(Flip::Vertical), Point(2, 5));
+        assert_eq!(p.transposed(), Point(-5, 2));
+    }
+
     #[test]
     fn test_from_str() {
         assert_eq!("(-4, 9)".parse::<Point>().unwrap(), Point(-4, 9));
@@ -0,0 +1,211 @@
+use std::fs::read_to_string;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use catalog::SAMPLES;
+use grid::{Grid, Point};
+use transform::Transform;
+use {Error, ErrorKind, Result, ResultExt};
+
+/// Where the pattern for a Placement comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternSource {
+    /// The text of the pattern itself.
+    Inline(String),
+    /// A pattern file.
+    File(PathBuf),
+    /// One of the built-in sample patterns.
+    Sample(String),
+    /// A named pattern from a pattern library, e.g. the web server's.
+    Library(String),
+}
+
+/// A PatternResolver loads the text of the pattern that a PatternSource refers to.
+pub trait PatternResolver {
+    fn resolve(&self, source: &PatternSource) -> Result<String>;
+}
+
+/// Resolves inline patterns, files and samples. There is no library to resolve names from.
+pub struct LocalResolver;
+
+impl PatternResolver for LocalResolver {
+    fn resolve(&self, source: &PatternSource) -> Result<String> {
+        match source {
+            PatternSource::Inline(pattern) => Ok(pattern.clone()),
+            PatternSource::File(path) => read_to_string(path)
+                .chain_err(|| format!("failed to read pattern file {}", path.display())),
+            PatternSource::Sample(name) => match SAMPLES.get(name) {
+                Some(entry) => Ok(entry.source.clone()),
+                None => bail!(ErrorKind::InvalidConfig(
+                    "scene",
+                    format!("unknown sample pattern '{}'", name)
+                )),
+            },
+            PatternSource::Library(name) => bail!(ErrorKind::InvalidConfig(
+                "scene",
+                format!("can't load '{}': no pattern library is available", name)
+            )),
+        }
+    }
+}
+
+/// A Placement puts a pattern into a scene at a given offset and orientation.
+///
+/// It can be parsed from a comma-separated list of `key=value` pairs, e.g.
+/// `sample=glider,x=10,y=-4,rotate=90,flip=h,transpose,label=gun`. Exactly one of `inline`,
+/// `file`, `sample` or `library` gives the source; rows of an inline pattern are separated by
+/// `/`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Placement {
+    pub source: PatternSource,
+    /// Where the top-left corner of the pattern goes, after the transform is applied.
+    #[serde(default)]
+    pub offset: Point,
+    #[serde(default)]
+    pub transform: Transform,
+    /// A name for the Placement, e.g. to show in a UI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Placement {
+    /// Create a Placement of the given pattern at the origin, with no transform.
+    pub fn new(source: PatternSource) -> Self {
+        Placement {
+            source,
+            offset: Point::origin(),
+            transform: Transform::default(),
+            label: None,
+        }
+    }
+
+    /// Load the pattern, and return it transformed and moved into place.
+    pub fn load<R: PatternResolver>(&self, resolver: &R) -> Result<Grid> {
+        let grid: Grid = resolver.resolve(&self.source)?.parse()?;
+        let grid = grid.transformed(&self.transform);
+        match grid.checked_translated(self.offset) {
+            Some(grid) => Ok(grid),
+            None => bail!(ErrorKind::InvalidConfig(
+                "offset",
+                format!("{} moves the pattern out of range", self.offset)
+            )),
+        }
+    }
+}
+
+impl FromStr for Placement {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: String| ErrorKind::InvalidConfig("place", reason);
+        let mut source = None;
+        let mut placement = Placement::new(PatternSource::Inline(String::new()));
+
+        for part in s.split(',') {
+            let mut kv = part.splitn(2, '=');
+            let (key, value) = (kv.next().unwrap().trim(), kv.next().map(str::trim));
+            let parse_int = |value: &str| {
+                value
+                    .parse::<i64>()
+                    .map_err(|_| invalid(format!("expected an integer for '{}'", key)))
+            };
+            if source.is_some() && ["inline", "file", "sample", "library"].contains(&key) {
+                bail!(invalid("only one pattern source can be given".to_string()));
+            }
+            match (key, value) {
+                ("inline", Some(value)) => {
+                    source = Some(PatternSource::Inline(value.replace('/', "\n")))
+                }
+                ("file", Some(value)) => source = Some(PatternSource::File(PathBuf::from(value))),
+                ("sample", Some(value)) => source = Some(PatternSource::Sample(value.to_string())),
+                ("library", Some(value)) => {
+                    source = Some(PatternSource::Library(value.to_string()))
+                }
+                ("x", Some(value)) => placement.offset.0 = parse_int(value)?,
+                ("y", Some(value)) => placement.offset.1 = parse_int(value)?,
+                ("rotate", Some(value)) => placement.transform.rotate = Some(value.parse()?),
+                ("flip", Some(value)) => placement.transform.flip = Some(value.parse()?),
+                ("transpose", None) => placement.transform.transpose = true,
+                ("label", Some(value)) => placement.label = Some(value.to_string()),
+                _ => bail!(invalid(format!("unexpected '{}'", part.trim()))),
+            }
+        }
+
+        placement.source = source.ok_or_else(|| {
+            invalid("expected a pattern source: inline, file, sample or library".to_string())
+        })?;
+        Ok(placement)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use transform::{Flip, Rotation};
+
+    #[test]
+    fn test_parse() {
+        let placement: Placement = "sample=glider, x=10,y=-4,rotate=90,flip=h,transpose,label=gun"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            placement,
+            Placement {
+                source: PatternSource::Sample("glider".to_string()),
+                offset: Point(10, -4),
+                transform: Transform {
+                    transpose: true,
+                    flip: Some(Flip::Horizontal),
+                    rotate: Some(Rotation::R90),
+                    ..Default::default()
+                },
+                label: Some("gun".to_string()),
+            }
+        );
+        assert_eq!(
+            "inline=xx./..x".parse::<Placement>().unwrap().source,
+            PatternSource::Inline("xx.\n..x".to_string())
+        );
+        for s in &[
+            "x=1,y=2",
+            "sample=glider,file=glider",
+            "sample=glider,x=one",
+            "sample=glider,rotate=45",
+            "sample=glider,size=big",
+        ] {
+            assert!(s.parse::<Placement>().is_err(), "should reject '{}'", s);
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let placement = Placement {
+            offset: Point(-5, 2),
+            transform: Transform {
+                rotate: Some(Rotation::R90),
+                ..Default::default()
+            },
+            ..Placement::new(PatternSource::Inline("xxx\nx..".to_string()))
+        };
+        assert_eq!(
+            placement.load(&LocalResolver).unwrap(),
+            Grid::new(vec![Point(-5, 2), Point(-4, 2), Point(-4, 3), Point(-4, 4)])
+        );
+        assert!(Placement::new(PatternSource::Sample("glider".to_string()))
+            .load(&LocalResolver)
+            .is_ok());
+        assert!(Placement::new(PatternSource::Library("glider".to_string()))
+            .load(&LocalResolver)
+            .is_err());
+
+        let far = Placement {
+            offset: Point(i64::MAX, 0),
+            ..Placement::new(PatternSource::Inline("xx".to_string()))
+        };
+        match far.load(&LocalResolver).unwrap_err().kind() {
+            ErrorKind::InvalidConfig(field, _) => assert_eq!(*field, "offset"),
+            kind => panic!("unexpected error: {}", kind),
+        }
+    }
+}
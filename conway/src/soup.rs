@@ -0,0 +1,292 @@
+use std::fmt;
+use std::str::FromStr;
+
+use grid::{Grid, Point};
+use {Error, ErrorKind, Result};
+
+/// A Symmetry that can be imposed on a random soup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Symmetry {
+    /// No symmetry.
+    C1,
+    /// Symmetric under 180 degree rotation.
+    C2,
+    /// Symmetric under 90 degree rotation. Requires a square soup.
+    C4,
+    /// Symmetric under horizontal and vertical reflection.
+    D4,
+    /// Symmetric under all rotations and reflections. Requires a square soup.
+    D8,
+}
+
+impl Symmetry {
+    /// Return whether the Symmetry only works for square soups.
+    pub fn needs_square(self) -> bool {
+        match self {
+            Symmetry::C4 | Symmetry::D8 => true,
+            Symmetry::C1 | Symmetry::C2 | Symmetry::D4 => false,
+        }
+    }
+
+    /// Return every image of the given Point under the Symmetry, in a `width` by `height` box.
+    fn orbit(self, Point(x, y): Point, width: i64, height: i64) -> Vec<Point> {
+        let (x1, y1) = (width - 1, height - 1);
+        match self {
+            Symmetry::C1 => vec![Point(x, y)],
+            Symmetry::C2 => vec![Point(x, y), Point(x1 - x, y1 - y)],
+            Symmetry::C4 => vec![
+                Point(x, y),
+                Point(x1 - y, x),
+                Point(x1 - x, y1 - y),
+                Point(y, y1 - x),
+            ],
+            Symmetry::D4 => vec![
+                Point(x, y),
+                Point(x1 - x, y),
+                Point(x, y1 - y),
+                Point(x1 - x, y1 - y),
+            ],
+            Symmetry::D8 => vec![
+                Point(x, y),
+                Point(x1 - y, x),
+                Point(x1 - x, y1 - y),
+                Point(y, y1 - x),
+                Point(x1 - x, y),
+                Point(x, y1 - y),
+                Point(y, x),
+                Point(x1 - y, y1 - x),
+            ],
+        }
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_uppercase().as_str() {
+            "C1" => Ok(Symmetry::C1),
+            "C2" => Ok(Symmetry::C2),
+            "C4" => Ok(Symmetry::C4),
+            "D4" => Ok(Symmetry::D4),
+            "D8" => Ok(Symmetry::D8),
+            _ => bail!("invalid value for symmetry '{}'", s),
+        }
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// A SoupSpec describes a reproducible random soup.
+///
+/// It can be parsed from and displayed as `WxH:density:seed[:symmetry]`, e.g. `16x16:0.5:42:C2`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoupSpec {
+    pub width: u64,
+    pub height: u64,
+    /// The chance that each cell starts out alive, from 0 to 1.
+    pub density: f64,
+    pub seed: u64,
+    #[serde(default = "default_symmetry")]
+    pub symmetry: Symmetry,
+}
+
+fn default_symmetry() -> Symmetry {
+    Symmetry::C1
+}
+
+impl SoupSpec {
+    /// Create a new SoupSpec with no symmetry.
+    pub fn new(width: u64, height: u64, density: f64, seed: u64) -> Self {
+        SoupSpec {
+            width,
+            height,
+            density,
+            seed,
+            symmetry: Symmetry::C1,
+        }
+    }
+
+    /// Impose the given Symmetry on the soup.
+    pub fn with_symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    /// Check that a soup can be generated from the SoupSpec.
+    pub fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            bail!(ErrorKind::InvalidConfig(
+                "soup",
+                "width and height must be greater than 0".to_string()
+            ));
+        }
+        if !(self.density >= 0.0 && self.density <= 1.0) {
+            bail!(ErrorKind::InvalidConfig(
+                "soup",
+                "density must be between 0 and 1".to_string()
+            ));
+        }
+        if self.symmetry.needs_square() && self.width != self.height {
+            bail!(ErrorKind::InvalidConfig(
+                "soup",
+                format!("{} symmetry requires a square soup", self.symmetry)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Generate the soup. The same SoupSpec always generates the same Grid.
+    ///
+    /// Panics if the SoupSpec is invalid.
+    pub fn generate(&self) -> Grid {
+        self.validate().expect("invalid soup");
+        let (width, height) = (self.width as i64, self.height as i64);
+        let mut rng = SplitMix64::new(self.seed);
+        let mut grid = Grid::empty();
+        for y in 0..height {
+            for x in 0..width {
+                // Only draw a random value for the first Point in each orbit, and copy it to
+                // the rest, so that the soup is symmetric.
+                let orbit = self.symmetry.orbit(Point(x, y), width, height);
+                if orbit.iter().any(|&Point(ox, oy)| (oy, ox) < (y, x)) {
+                    continue;
+                }
+                if rng.next_f64() < self.density {
+                    for point in orbit {
+                        grid.set_alive(point);
+                    }
+                }
+            }
+        }
+        grid
+    }
+}
+
+impl FromStr for SoupSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            ErrorKind::InvalidConfig(
+                "soup",
+                format!("expected WxH:density:seed[:symmetry], got '{}'", s),
+            )
+        };
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        if parts.len() < 3 || parts.len() > 4 {
+            bail!(invalid());
+        }
+        let (width, height) = parse_size(parts[0]).ok_or_else(invalid)?;
+        let spec = SoupSpec {
+            width,
+            height,
+            density: parts[1].parse().map_err(|_| invalid())?,
+            seed: parts[2].parse().map_err(|_| invalid())?,
+            symmetry: match parts.get(3) {
+                Some(symmetry) => symmetry.parse()?,
+                None => Symmetry::C1,
+            },
+        };
+        spec.validate()?;
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for SoupSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}:{}:{}:{}",
+            self.width, self.height, self.density, self.seed, self.symmetry
+        )
+    }
+}
+
+/// Parse a size given as `WxH`, e.g. `16x8`.
+pub fn parse_size(s: &str) -> Option<(u64, u64)> {
+    let mut size = s.splitn(2, &['x', 'X'][..]);
+    match (size.next(), size.next()) {
+        (Some(width), Some(height)) => Some((width.parse().ok()?, height.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// A small, fast PRNG (SplitMix64). It's implemented here rather than taken from a crate so that
+/// soups stay the same across versions.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Return a random float in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let spec: SoupSpec = "16x8:0.5:42".parse().unwrap();
+        assert_eq!(spec, SoupSpec::new(16, 8, 0.5, 42));
+        assert_eq!(spec.to_string(), "16x8:0.5:42:C1");
+        assert_eq!(
+            "4x4:1:7:d8".parse::<SoupSpec>().unwrap().symmetry,
+            Symmetry::D8
+        );
+        for s in &[
+            "16x8:0.5",
+            "16:0.5:42",
+            "16x8:2:42",
+            "16x8:0.5:42:C4",
+            "0x8:0.5:1",
+        ] {
+            assert!(s.parse::<SoupSpec>().is_err(), "should reject '{}'", s);
+        }
+    }
+
+    // The same spec should always generate the same soup, and different seeds different soups.
+    #[test]
+    fn test_generate_reproducible() {
+        let spec = SoupSpec::new(16, 16, 0.5, 1);
+        assert_eq!(spec.generate(), spec.generate());
+        assert_ne!(spec.generate(), SoupSpec::new(16, 16, 0.5, 2).generate());
+        assert!(SoupSpec::new(8, 8, 0.0, 1).generate().is_empty());
+        assert_eq!(SoupSpec::new(8, 4, 1.0, 1).generate().len(), 32);
+    }
+
+    #[test]
+    fn test_generate_symmetric() {
+        for &symmetry in &[Symmetry::C2, Symmetry::C4, Symmetry::D4, Symmetry::D8] {
+            let grid = SoupSpec::new(9, 9, 0.5, 3)
+                .with_symmetry(symmetry)
+                .generate();
+            for y in 0..9 {
+                for x in 0..9 {
+                    let alive = grid.is_alive(&Point(x, y));
+                    for point in symmetry.orbit(Point(x, y), 9, 9) {
+                        assert_eq!(grid.is_alive(&point), alive, "{} soup", symmetry);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+use std::time::Duration;
+
+use grid::{Grid, Point};
+
+/// Stats records what happened to a Game's grid during a single turn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    /// The generation the grid is on after the turn.
+    pub generation: u64,
+    /// The number of live cells.
+    pub population: usize,
+    /// The number of cells that came to life during the turn.
+    pub births: usize,
+    /// The number of cells that died during the turn.
+    pub deaths: usize,
+    /// The lowest and highest coordinates of live cells, or `None` if there are none.
+    pub bounds: Option<(Point, Point)>,
+    /// How long the turn took to compute.
+    pub duration: Duration,
+}
+
+impl Stats {
+    /// Create Stats for the given grid.
+    pub fn new(
+        generation: u64,
+        grid: &Grid,
+        births: usize,
+        deaths: usize,
+        duration: Duration,
+    ) -> Self {
+        Stats {
+            generation,
+            population: grid.len(),
+            births,
+            deaths,
+            bounds: if grid.is_empty() {
+                None
+            } else {
+                Some(grid.bounds())
+            },
+            duration,
+        }
+    }
+
+    /// Create Stats for a grid that hasn't had a turn yet, e.g. at the start of a Game.
+    pub fn initial(generation: u64, grid: &Grid) -> Self {
+        Stats::new(generation, grid, 0, 0, Duration::from_secs(0))
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "generation {} | population {} (+{} -{})",
+            self.generation, self.population, self.births, self.deaths
+        )?;
+        if let Some((p0, p1)) = self.bounds {
+            write!(f, " | bounds {} to {}", p0, p1)?;
+        }
+        write!(f, " | {:.2}ms", self.duration.as_secs_f64() * 1000.0)
+    }
+}
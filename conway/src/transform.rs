@@ -0,0 +1,149 @@
+use std::fmt;
+use std::str::FromStr;
+
+use grid::{Grid, Point};
+use {Error, Result};
+
+/// A clockwise Rotation by a multiple of 90 degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Rotation {
+    #[serde(rename = "90")]
+    R90,
+    #[serde(rename = "180")]
+    R180,
+    #[serde(rename = "270")]
+    R270,
+}
+
+impl FromStr for Rotation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "90" => Ok(Rotation::R90),
+            "180" => Ok(Rotation::R180),
+            "270" => Ok(Rotation::R270),
+            _ => bail!("invalid value for rotation '{}'", s),
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rotation::R90 => write!(f, "90"),
+            Rotation::R180 => write!(f, "180"),
+            Rotation::R270 => write!(f, "270"),
+        }
+    }
+}
+
+/// A Flip mirrors a pattern, either left to right (`Horizontal`) or top to bottom (`Vertical`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flip {
+    Horizontal,
+    Vertical,
+}
+
+impl FromStr for Flip {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "h" | "horizontal" => Ok(Flip::Horizontal),
+            "v" | "vertical" => Ok(Flip::Vertical),
+            _ => bail!("invalid value for flip '{}'", s),
+        }
+    }
+}
+
+impl fmt::Display for Flip {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Flip::Horizontal => write!(f, "horizontal"),
+            Flip::Vertical => write!(f, "vertical"),
+        }
+    }
+}
+
+/// A Transform combines the geometric transforms that can be applied to a Grid.
+///
+/// They are applied in the order the fields are listed: the pattern is transposed, then flipped,
+/// then rotated, and finally translated. The default Transform leaves a Grid unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub transpose: bool,
+    pub flip: Option<Flip>,
+    pub rotate: Option<Rotation>,
+    pub translate: Point,
+}
+
+impl Transform {
+    /// Return whether the Transform leaves a Grid unchanged.
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::default()
+    }
+
+    /// Return whether the Transform swaps a pattern's width and height.
+    pub fn swaps_axes(&self) -> bool {
+        let rotated = match self.rotate {
+            Some(Rotation::R90) | Some(Rotation::R270) => true,
+            Some(Rotation::R180) | None => false,
+        };
+        self.transpose != rotated
+    }
+
+    /// Apply the Transform to the Grid in place.
+    pub fn apply(&self, grid: &mut Grid) {
+        if self.transpose {
+            grid.transpose();
+        }
+        if let Some(flip) = self.flip {
+            grid.flip(flip);
+        }
+        if let Some(rotation) = self.rotate {
+            grid.rotate(rotation);
+        }
+        grid.translate(self.translate);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("270".parse::<Rotation>().unwrap(), Rotation::R270);
+        assert!("45".parse::<Rotation>().is_err());
+        assert_eq!("H".parse::<Flip>().unwrap(), Flip::Horizontal);
+        assert_eq!("vertical".parse::<Flip>().unwrap(), Flip::Vertical);
+        assert!("x".parse::<Flip>().is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut grid: Grid = "xxx\nx..".parse().unwrap();
+        let transform = Transform {
+            transpose: true,
+            flip: Some(Flip::Vertical),
+            rotate: Some(Rotation::R180),
+            translate: Point(2, -1),
+        };
+        assert!(transform.swaps_axes());
+        transform.apply(&mut grid);
+        // Transposed: xx/x./x., flipped: x./x./xx, rotated: xx/.x/.x.
+        assert_eq!(
+            grid,
+            Grid::new(vec![Point(2, -1), Point(3, -1), Point(3, 0), Point(3, 1)])
+        );
+
+        let mut grid: Grid = "xx.\n.xx".parse().unwrap();
+        let original = grid.clone();
+        Transform::default().apply(&mut grid);
+        assert_eq!(grid, original);
+        assert!(Transform::default().is_identity());
+    }
+}